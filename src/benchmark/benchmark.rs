@@ -1,5 +1,7 @@
+use crate::utils::cpu_affinity::CpuSet;
 use crate::utils::docker_runner::run_docker_compose;
 use crate::utils::percentile;
+use crate::utils::percentile::Histogram;
 use crate::utils::version_migrator::VersionMigrator;
 use indexmap::IndexMap;
 use std::fmt::{Debug, Display};
@@ -26,16 +28,32 @@ networks:
     external: true
 "#;
 
+/// The container is pinned to the given cores in addition to the CPU limit.
+pub fn compose_file(container_cpuset: Option<&CpuSet>) -> String {
+    match container_cpuset {
+        Some(cpuset) => COMPOSE_FILE.replacen(
+            "    deploy:",
+            &format!("    cpuset: \"{}\"\n    deploy:", cpuset),
+            1,
+        ),
+        None => COMPOSE_FILE.to_string(),
+    }
+}
+
 pub struct BenchmarkResult {
     pub time_median: i64,
     pub memory_median: i64,
     pub memory_p99: i64,
     pub additional_data: IndexMap<String, AdditionalData>,
+
+    /// Merged latency histogram of all (non-warmup) iterations.
+    pub latency_histogram: Option<Histogram>,
 }
 
 pub struct IterationResult {
     pub additional_data: IndexMap<String, AdditionalData>,
     pub debugging_data: IndexMap<String, AdditionalData>,
+    pub latency_histogram: Option<Histogram>,
 }
 
 #[derive(Clone)]
@@ -72,6 +90,7 @@ fn format_additional_data(
 pub fn run_benchmark<F>(
     dir: &str,
     stats_reader: &mut crate::utils::docker_stats::DockerStatsReader,
+    container_cpuset: Option<&CpuSet>,
     mut version_migrations: Vec<&mut VersionMigrator>,
     warmup_rounds: usize,
     rounds: usize,
@@ -88,8 +107,10 @@ where
     let mut memory_median: Vec<i64> = Vec::new();
     let mut memory_p99: Vec<i64> = Vec::new();
     let mut additional_data: Vec<IndexMap<String, AdditionalData>> = Vec::new();
+    let mut latency_histogram: Option<Histogram> = None;
 
-    run_docker_compose(dir, Duration::from_secs(5), Some(COMPOSE_FILE), || {
+    let compose_file = compose_file(container_cpuset);
+    run_docker_compose(dir, Duration::from_secs(5), Some(&compose_file), || {
         println!(" -> Running benchmark");
         let mut fail_count = 0;
         let mut warmup_counter = 0;
@@ -146,6 +167,11 @@ where
             memory_median.push(memory_usage.median);
             memory_p99.push(memory_usage.p99);
             additional_data.push(result.additional_data);
+            if let Some(histogram) = result.latency_histogram {
+                latency_histogram
+                    .get_or_insert_with(Histogram::new)
+                    .merge(&histogram);
+            }
 
             // Wait for 2 seconds to let the container cool down
             thread::sleep(Duration::from_secs(2));
@@ -195,12 +221,13 @@ where
 
     memory_median.sort();
     memory_p99.sort();
-    return BenchmarkResult {
+    BenchmarkResult {
         time_median,
         memory_median: percentile::p50(&memory_median),
         memory_p99: percentile::p99(&memory_p99),
         additional_data: additional_data_median,
-    };
+        latency_histogram,
+    }
 }
 
 trait SizeFormat {
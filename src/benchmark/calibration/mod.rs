@@ -0,0 +1,138 @@
+use crate::benchmark::web::WebBenchmarkOptions;
+use crate::utils::cpu_affinity::CpuSet;
+use crate::utils::http_load_tester::{
+    run_http_load_test, ConnectionMode, HttpLoadConfig, HttpLoadResult, LoadMode,
+    PendingValidationResponse, PreparedHttpRequest, Protocol,
+};
+use crate::utils::load_engine::Engine;
+use clap::ValueEnum;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const CONCURRENCY: usize = 32;
+const DURATION: Duration = Duration::from_secs(5);
+const ROUTE: &str = "noop";
+
+/// Runs the load generator against an in-process server that answers every request immediately.
+/// The result is the ceiling of the load generator on this machine:
+/// frameworks that come close to it are limited by the load generator, not by themselves.
+/// The server runs on the cores of the container, the load generator on its own cores.
+pub fn calibrate(options: &WebBenchmarkOptions, verbose: bool) {
+    let addr = start_noop_server(options.container_cpuset.clone());
+    let load_threads = options.load_threads();
+    println!(
+        " -> Calibrating against no-op server on {} with {} load generator threads",
+        addr, load_threads
+    );
+
+    let engines: Vec<Engine> = match options.engine {
+        Some(engine) => vec![engine],
+        None => Engine::value_variants()
+            .iter()
+            .filter(|engine| !engine.is_external())
+            .copied()
+            .collect(),
+    };
+    let requests = vec![PreparedHttpRequest {
+        route: ROUTE.to_string(),
+        url: format!("http://{}/{}", addr, ROUTE),
+        ..Default::default()
+    }];
+
+    for engine in engines {
+        for protocol in [Protocol::Http1, Protocol::H2c] {
+            if !engine.supports(protocol) {
+                continue;
+            }
+            let result = run_http_load_test(
+                &HttpLoadConfig {
+                    max_in_flight: CONCURRENCY,
+                    protocol,
+                    connections: match protocol {
+                        Protocol::Http1 => CONCURRENCY,
+                        Protocol::H2c => 1,
+                    },
+                    connection_mode: ConnectionMode::KeepAlive,
+                    engine,
+                    worker_threads: load_threads,
+                    cpuset: options.load_cpuset.clone(),
+                    duration: DURATION,
+                    ramp_up: Duration::ZERO,
+                    exclude_start: Duration::from_secs(1),
+                    exclude_end: Duration::ZERO,
+                    load_mode: LoadMode::Closed,
+                    target_rps: None,
+                    validation: Default::default(),
+                    route_weights: None,
+                    schedule: None,
+                    trace_path: None,
+                    error_budget: 0.0,
+                    seed: 0,
+                    base_seed: 0,
+                    iteration: 0,
+                    verbose,
+                },
+                &requests,
+                noop_validator,
+            );
+            print_calibration_result(engine, protocol, &result);
+        }
+    }
+}
+
+fn print_calibration_result(engine: Engine, protocol: Protocol, result: &HttpLoadResult) {
+    println!(
+        " -> {} ({}): {} req/s, latency p50 = {:?}, p99 = {:?}",
+        engine, protocol, result.rps_median, result.latency_median, result.latency_p99
+    );
+}
+
+fn noop_validator(_: &PendingValidationResponse) -> Result<(), String> {
+    Ok(())
+}
+
+/// Starts a HTTP/1.1 and h2c server on a random local port in a background thread.
+/// It has its own runtime, so it does not share worker threads with the load generator.
+fn start_noop_server(cpuset: Option<CpuSet>) -> SocketAddr {
+    let (addr_tx, addr_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(cpuset) = cpuset {
+            builder.on_thread_start(move || cpuset.pin_current_thread());
+        }
+        let rt = builder.build().unwrap();
+        rt.block_on(async move {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("Failed to start no-op server");
+            addr_tx.send(listener.local_addr().unwrap()).unwrap();
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                stream.set_nodelay(true).unwrap();
+                tokio::spawn(async move {
+                    let _ = auto::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service_fn(noop_handler))
+                        .await;
+                });
+            }
+        });
+    });
+    addr_rx.recv().unwrap()
+}
+
+async fn noop_handler(_: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    Ok(Response::new(Full::new(Bytes::from_static(b"{}"))))
+}
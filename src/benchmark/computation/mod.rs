@@ -19,7 +19,7 @@ pub fn benchmark_computation(
     validate: bool,
 ) {
     let meta_data: BenchmarkMetaData = BenchmarkMetaData::read_from_directory(dir)
-        .expect(&format!("Failed to read meta data: {dir}"));
+        .unwrap_or_else(|_| panic!("Failed to read meta data: {dir}"));
 
     // Early check if all existing results are in metadata to avoid printing metadata info
     if let Some(existing) = existing {
@@ -59,7 +59,7 @@ pub fn benchmark_computation(
         }
 
         if let Some(copy_files) = &meta_data.copy {
-            copy_files::copy_files(dir, &copy_files);
+            copy_files::copy_files(dir, copy_files);
         }
 
         let mut version_migrations: Vec<VersionMigrator> = match meta_data.language_version.len() {
@@ -74,6 +74,7 @@ pub fn benchmark_computation(
         let result = run_benchmark(
             dir,
             stats_reader,
+            None,
             version_migrations.iter_mut().collect(),
             match validate {
                 true => 0,
@@ -105,12 +106,13 @@ pub fn benchmark_computation(
                 Ok(IterationResult {
                     additional_data: IndexMap::new(),
                     debugging_data: IndexMap::new(),
+                    latency_histogram: None,
                 })
             },
         );
 
         if let Some(copy_files) = &meta_data.copy {
-            copy_files::delete_copied_files(dir, &copy_files);
+            copy_files::delete_copied_files(dir, copy_files);
         }
 
         if validate {
@@ -129,6 +131,7 @@ pub fn benchmark_computation(
                 ("time_median", result.time_median.to_string().as_str()),
                 ("memory_median", result.memory_median.to_string().as_str()),
             ]),
+            &[],
             take_lower_time_median,
         )
         .expect("Failed to write result to file");
@@ -1,3 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod benchmark;
+pub mod calibration;
 pub mod computation;
 pub mod web;
@@ -0,0 +1,363 @@
+use crate::benchmark::benchmark::compose_file;
+use crate::benchmark::web::{
+    element_expectation, expected_headers, load_data, set_data_rotation, set_upstream_latency,
+    shells_expectation, PeriodicTableElement,
+};
+use crate::utils::copy_files;
+use crate::utils::cpu_affinity::CpuSet;
+use crate::utils::docker_runner::run_docker_compose;
+use crate::utils::expectation::{Expectation, HeaderExpectation};
+use crate::utils::http_load_tester::CLIENT_TIMEOUT;
+use crate::utils::meta_data_parser::{CopyValue, WebBenchmarkMetaData};
+use crate::utils::upstream_latency::LatencyProfile;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use reqwest::{Method, StatusCode};
+use std::thread;
+use std::time::Duration;
+
+const BASE_URL: &str = "http://localhost:3000";
+
+/// Number of requests sent at the same time right after the container has started.
+const CONCURRENT_FIRST_REQUESTS: usize = 16;
+
+/// Length of the padding in the huge query string.
+const HUGE_QUERY_LENGTH: usize = 16 * 1024;
+
+pub struct CheckResult {
+    pub name: &'static str,
+
+    /// Required checks follow from the API spec, the others are recommended behavior.
+    pub required: bool,
+    pub result: Result<(), String>,
+}
+
+pub struct ConformanceReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    pub fn passed_required(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| !check.required || check.result.is_ok())
+    }
+}
+
+/// Starts the container of the benchmark and probes the edge cases of the API spec.
+/// The web data source must already be running.
+pub fn conform(dir: &str, container_cpuset: Option<&CpuSet>) -> ConformanceReport {
+    let meta_data: WebBenchmarkMetaData = WebBenchmarkMetaData::read_from_directory(dir)
+        .unwrap_or_else(|_| panic!("Failed to read meta data: {dir}"));
+    let _copied_files = meta_data
+        .copy
+        .as_ref()
+        .map(|files| CopiedFiles::copy(dir, files));
+
+    // The checks expect the static data.
+    set_data_rotation(None);
+    set_upstream_latency(LatencyProfile::None);
+
+    let mut report = ConformanceReport { checks: Vec::new() };
+    let compose_file = compose_file(container_cpuset);
+    run_docker_compose(dir, Duration::from_secs(5), Some(&compose_file), || {
+        println!(" -> Probing edge cases");
+        report = probe();
+    });
+
+    println!("{}", format_report(dir, &report));
+    report
+}
+
+/// Deletes the copied files when dropped, also if a probe panics.
+struct CopiedFiles<'a> {
+    dir: &'a str,
+    files: &'a Vec<CopyValue>,
+}
+
+impl<'a> CopiedFiles<'a> {
+    fn copy(dir: &'a str, files: &'a Vec<CopyValue>) -> CopiedFiles<'a> {
+        copy_files::copy_files(dir, files);
+        CopiedFiles { dir, files }
+    }
+}
+
+impl Drop for CopiedFiles<'_> {
+    fn drop(&mut self) {
+        copy_files::delete_copied_files(self.dir, self.files);
+    }
+}
+
+fn probe() -> ConformanceReport {
+    let client = Client::builder().timeout(CLIENT_TIMEOUT).build().unwrap();
+    let data = load_data();
+    let helium = &data["He"];
+    let headers = expected_headers();
+    let element =
+        |symbol: &str| format!("{BASE_URL}/api/v1/periodic-table/element?symbol={symbol}");
+    let shells = |symbol: &str| format!("{BASE_URL}/api/v1/periodic-table/shells?symbol={symbol}");
+
+    let mut checks = Vec::new();
+    let mut check = |name: &'static str, required: bool, result: Result<(), String>| {
+        checks.push(CheckResult {
+            name,
+            required,
+            result,
+        });
+    };
+
+    // Must be first, as some frameworks initialize lazily on the first request.
+    check(
+        "concurrent first requests",
+        true,
+        concurrent_first_requests(&client, &data),
+    );
+    check(
+        "element",
+        true,
+        expect_ok(
+            client.get(element("He")).send(),
+            &headers,
+            &element_expectation(helium),
+        ),
+    );
+    check(
+        "shells",
+        true,
+        expect_ok(
+            client.get(shells("He")).send(),
+            &headers,
+            &shells_expectation(helium),
+        ),
+    );
+    check(
+        "url-encoded symbol",
+        true,
+        expect_ok(
+            client.get(element("%48%65")).send(),
+            &headers,
+            &element_expectation(helium),
+        ),
+    );
+    check(
+        "content length",
+        false,
+        expect_content_length(client.get(element("He")).send()),
+    );
+    check(
+        "unknown route",
+        false,
+        expect_status(
+            client.get(format!("{BASE_URL}/api/v1/unknown")).send(),
+            |status| status == StatusCode::NOT_FOUND,
+            "404",
+        ),
+    );
+    check(
+        "unknown symbol",
+        false,
+        expect_status(
+            client.get(element("Xx")).send(),
+            |status| status.is_client_error(),
+            "4xx",
+        ),
+    );
+    check(
+        "missing symbol",
+        false,
+        expect_status(
+            client
+                .get(format!("{BASE_URL}/api/v1/periodic-table/element"))
+                .send(),
+            |status| status.is_client_error(),
+            "4xx",
+        ),
+    );
+    check(
+        "HEAD request",
+        false,
+        expect_status(
+            client.request(Method::HEAD, element("He")).send(),
+            |status| status == StatusCode::OK,
+            "200",
+        ),
+    );
+    check(
+        "huge query string",
+        false,
+        expect_status(
+            client
+                .get(format!(
+                    "{}&padding={}",
+                    element("He"),
+                    "x".repeat(HUGE_QUERY_LENGTH)
+                ))
+                .send(),
+            |status| {
+                matches!(
+                    status,
+                    StatusCode::OK
+                        | StatusCode::BAD_REQUEST
+                        | StatusCode::URI_TOO_LONG
+                        | StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+                )
+            },
+            "200, 400, 414 or 431",
+        ),
+    );
+    check(
+        "alive after edge cases",
+        true,
+        expect_ok(
+            client.get(element("He")).send(),
+            &headers,
+            &element_expectation(helium),
+        ),
+    );
+
+    ConformanceReport { checks }
+}
+
+/// Sends `CONCURRENT_FIRST_REQUESTS` requests for different elements at the same time.
+/// All of them must be answered correctly.
+fn concurrent_first_requests(
+    client: &Client,
+    data: &std::collections::HashMap<String, PeriodicTableElement>,
+) -> Result<(), String> {
+    let headers = expected_headers();
+    thread::scope(|scope| {
+        let handles: Vec<_> = data
+            .iter()
+            .take(CONCURRENT_FIRST_REQUESTS)
+            .map(|(symbol, element)| {
+                let headers = &headers;
+                scope.spawn(move || {
+                    let url = format!("{BASE_URL}/api/v1/periodic-table/element?symbol={symbol}");
+                    expect_ok(
+                        client.get(&url).send(),
+                        headers,
+                        &element_expectation(element),
+                    )
+                    .map_err(|e| format!("{symbol}: {e}"))
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Result<Vec<()>, String>>()
+            .map(|_| ())
+    })
+}
+
+fn expect_ok(
+    response: reqwest::Result<Response>,
+    headers: &HeaderExpectation,
+    body: &Expectation,
+) -> Result<(), String> {
+    let response = response.map_err(|e| format!("Request failed: {e}"))?;
+    if response.status() != StatusCode::OK {
+        return Err(format!("Expected status 200 but got {}", response.status()));
+    }
+    headers.check(response.headers())?;
+    let text = response
+        .text()
+        .map_err(|e| format!("Failed to read body: {e}"))?;
+    let json: serde_json::Value =
+        serde_json::from_str(&text).map_err(|_| format!("Failed to parse JSON: {text}"))?;
+    body.check(&json)
+}
+
+/// The responses are tiny, so they should not be sent as chunked body.
+fn expect_content_length(response: reqwest::Result<Response>) -> Result<(), String> {
+    let response = response.map_err(|e| format!("Request failed: {e}"))?;
+    if let Some(encoding) = response.headers().get(TRANSFER_ENCODING) {
+        return Err(format!(
+            "Expected a Content-Length but got \"Transfer-Encoding: {}\"",
+            String::from_utf8_lossy(encoding.as_bytes())
+        ));
+    }
+    match response.headers().contains_key(CONTENT_LENGTH) {
+        true => Ok(()),
+        false => Err("Expected header \"content-length\" but it does not exist".to_string()),
+    }
+}
+
+fn expect_status(
+    response: reqwest::Result<Response>,
+    accepted: impl Fn(StatusCode) -> bool,
+    description: &str,
+) -> Result<(), String> {
+    let response = response.map_err(|e| format!("Request failed: {e}"))?;
+    match accepted(response.status()) {
+        true => Ok(()),
+        false => Err(format!(
+            "Expected status {description} but got {}",
+            response.status()
+        )),
+    }
+}
+
+fn format_report(dir: &str, report: &ConformanceReport) -> String {
+    let mut lines = vec![
+        format!(" -> Conformance of {dir}:"),
+        format!("    {:<26} {:<12} {}", "Check", "Required", "Result"),
+    ];
+    for check in &report.checks {
+        let result = match &check.result {
+            Ok(()) => "pass".to_string(),
+            Err(e) => format!("FAIL ({})", e.lines().next().unwrap_or_default()),
+        };
+        let required = match check.required {
+            true => "required",
+            false => "recommended",
+        };
+        lines.push(format!(
+            "    {:<26} {:<12} {}",
+            check.name, required, result
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ConformanceReport {
+        ConformanceReport {
+            checks: vec![
+                CheckResult {
+                    name: "element",
+                    required: true,
+                    result: Ok(()),
+                },
+                CheckResult {
+                    name: "unknown symbol",
+                    required: false,
+                    result: Err("Expected status 4xx but got 500".to_string()),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn should_only_require_required_checks() {
+        let mut report = report();
+        assert!(report.passed_required());
+
+        report.checks[0].result = Err("Request failed".to_string());
+        assert!(!report.passed_required());
+    }
+
+    #[test]
+    fn should_format_matrix() {
+        assert_eq!(
+            format_report("benchmark/web/rust/axum", &report()),
+            " -> Conformance of benchmark/web/rust/axum:\n\
+             \x20   Check                      Required     Result\n\
+             \x20   element                    required     pass\n\
+             \x20   unknown symbol             recommended  FAIL (Expected status 4xx but got 500)"
+        );
+    }
+}
@@ -2,17 +2,43 @@ use http_body_util::Full;
 use hyper::body::Bytes;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{Request, Response};
+use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
+use serde_json::{Map, Value};
 use std::convert::Infallible;
 use std::fs;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seed and period of the data rotation. A period of 0 serves the static files.
+static ROTATION_SEED: AtomicU64 = AtomicU64::new(0);
+static ROTATION_PERIOD_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Requests and traffic since the last `/reset`.
+/// Only connections that fetched data are counted, so the control requests of the harness are left out.
+static ELEMENT_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static SHELLS_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+static OPEN_CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+static PEAK_CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Latency of the data files, set by `/latency`. `/latency` without a query restores the default from the environment.
+static LATENCY: RwLock<Latency> = RwLock::new(Latency::NONE);
+static DEFAULT_LATENCY: OnceLock<Latency> = OnceLock::new();
+static RANDOM_STATE: AtomicU64 = AtomicU64::new(0);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let element_file: &'static Bytes = Box::leak(Box::new(Bytes::from(fs::read_to_string("static/element.json").unwrap())));
     let shells_file: &'static Bytes = Box::leak(Box::new(Bytes::from(fs::read_to_string("static/shells.json").unwrap())));
-    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let data: &'static Data = Box::leak(Box::new(Data::read()));
+    let default_latency = Latency::parse(|name| std::env::var(format!("LATENCY_{}", name.to_uppercase())).ok()).unwrap_or_else(|error| panic!("{error}"));
+    *LATENCY.write().unwrap() = default_latency;
+    DEFAULT_LATENCY.set(default_latency).unwrap();
+    RANDOM_STATE.store(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64, Ordering::Relaxed);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:80").await.unwrap();
 
@@ -21,28 +47,71 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let io = TokioIo::new(stream);
 
         tokio::task::spawn(async move {
+            let fetched = AtomicBool::new(false);
+            let fetched = &fetched;
             if let Err(err) = http1::Builder::new()
                 .serve_connection(io, service_fn(|request: Request<hyper::body::Incoming>| async move {
                     let path: &str = request.uri().path();
+                    let mut status = StatusCode::OK;
+
+                    let body = match path {
+                        "/element.json" => {
+                            wait().await;
+                            ELEMENT_REQUESTS.fetch_add(1, Ordering::Relaxed);
+                            let body = match data.rotated() {
+                                Some((element, _)) => element,
+                                None => element_file.clone(),
+                            };
+                            count_fetch(fetched, body.len());
+                            body
+                        },
+                        "/shells.json" => {
+                            wait().await;
+                            SHELLS_REQUESTS.fetch_add(1, Ordering::Relaxed);
+                            let body = match data.rotated() {
+                                Some((_, shells)) => shells,
+                                None => shells_file.clone(),
+                            };
+                            count_fetch(fetched, body.len());
+                            body
+                        },
+                        "/reset" => {
+                            let value = ELEMENT_REQUESTS.swap(0, Ordering::SeqCst) + SHELLS_REQUESTS.swap(0, Ordering::SeqCst);
+                            CONNECTIONS.store(0, Ordering::SeqCst);
+                            PEAK_CONNECTIONS.store(OPEN_CONNECTIONS.load(Ordering::SeqCst), Ordering::SeqCst);
+                            BYTES.store(0, Ordering::SeqCst);
+                            Bytes::from(value.to_string())
+                        }
+                        "/stats" => Bytes::from(stats().to_string()),
+                        "/rotation" => {
+                            let query = request.uri().query().unwrap_or_default();
+                            ROTATION_SEED.store(query_param(query, "seed").unwrap_or(0), Ordering::SeqCst);
+                            ROTATION_PERIOD_MS.store(query_param(query, "period_ms").unwrap_or(0), Ordering::SeqCst);
+                            Bytes::from("ok".as_bytes())
+                        }
+                        "/latency" => {
+                            let latency = match request.uri().query() {
+                                Some(query) => Latency::parse(|name| query_param(query, name)),
+                                None => Ok(*DEFAULT_LATENCY.get().unwrap()),
+                            };
+                            match latency {
+                                Ok(latency) => {
+                                    *LATENCY.write().unwrap() = latency;
+                                    Bytes::from("ok".as_bytes())
+                                }
+                                Err(error) => {
+                                    status = StatusCode::BAD_REQUEST;
+                                    Bytes::from(error)
+                                }
+                            }
+                        }
+                        _ => Bytes::from("404 Not Found".as_bytes()),
+                    };
 
                     let response = Response::builder()
+                        .status(status)
                         .header("content-type", "application/json")
-                        .body(Full::new(match path {
-                            "/element.json" => {
-                                COUNTER.fetch_add(1, Ordering::Relaxed);
-                                element_file.clone()
-                            },
-                            "/shells.json" => {
-                                COUNTER.fetch_add(1, Ordering::Relaxed);
-                                shells_file.clone()
-                            },
-                            "/reset" => {
-                                let value = COUNTER.load(Ordering::SeqCst);
-                                COUNTER.store(0, Ordering::SeqCst);
-                                Bytes::from(value.to_string())
-                            }
-                            _ => Bytes::from("404 Not Found".as_bytes()),
-                        }))
+                        .body(Full::new(body))
                         .unwrap();
 
                     Ok::<Response<Full<Bytes>>, Infallible>(response)
@@ -51,6 +120,225 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             {
                 eprintln!("Error serving connection: {:?}", err);
             }
+            if fetched.load(Ordering::Relaxed) {
+                OPEN_CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+            }
         });
     }
 }
+
+/// Counts the bytes of a fetch, and the connection on its first fetch.
+fn count_fetch(fetched: &AtomicBool, bytes: usize) {
+    BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+    if !fetched.swap(true, Ordering::Relaxed) {
+        CONNECTIONS.fetch_add(1, Ordering::SeqCst);
+        let open = OPEN_CONNECTIONS.fetch_add(1, Ordering::SeqCst) + 1;
+        PEAK_CONNECTIONS.fetch_max(open, Ordering::SeqCst);
+    }
+}
+
+fn stats() -> Value {
+    serde_json::json!({
+        "requests": {
+            "/element.json": ELEMENT_REQUESTS.load(Ordering::SeqCst),
+            "/shells.json": SHELLS_REQUESTS.load(Ordering::SeqCst),
+        },
+        "connections": CONNECTIONS.load(Ordering::SeqCst),
+        "peak_connections": PEAK_CONNECTIONS.load(Ordering::SeqCst),
+        "bytes": BYTES.load(Ordering::SeqCst),
+        "latency": LATENCY.read().unwrap().describe(),
+    })
+}
+
+fn query_param<T: FromStr>(query: &str, name: &str) -> Option<T> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Jitter {
+    None,
+    Uniform { max_us: u64 },
+    LogNormal { median_us: u64, sigma: f64 },
+}
+
+/// Delay of the data files, to simulate an upstream that actually waits like a database.
+/// Parameters: `delay_us`, `jitter` (`none`, `uniform` or `lognormal`), `jitter_us` (maximum or median),
+/// `sigma` (log-normal only), `slow_rate` (fraction of slow responses) and `slow_us` (added to slow responses).
+#[derive(Clone, Copy, Debug)]
+struct Latency {
+    delay_us: u64,
+    jitter: Jitter,
+    slow_rate: f64,
+    slow_us: u64,
+}
+
+impl Latency {
+    const NONE: Latency = Latency { delay_us: 0, jitter: Jitter::None, slow_rate: 0.0, slow_us: 0 };
+
+    fn parse(param: impl Fn(&str) -> Option<String>) -> Result<Latency, String> {
+        let number = |name: &str, default: f64| match param(name) {
+            None => Ok(default),
+            Some(value) => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() && number >= 0.0 => Ok(number),
+                _ => Err(format!("Invalid latency parameter {name}: {value}")),
+            },
+        };
+        let jitter_us = number("jitter_us", 0.0)? as u64;
+        Ok(Latency {
+            delay_us: number("delay_us", 0.0)? as u64,
+            jitter: match param("jitter").as_deref() {
+                None | Some("none") => Jitter::None,
+                Some("uniform") => Jitter::Uniform { max_us: jitter_us },
+                Some("lognormal") => Jitter::LogNormal { median_us: jitter_us, sigma: number("sigma", 0.5)? },
+                Some(jitter) => return Err(format!("Invalid jitter: {jitter}")),
+            },
+            slow_rate: number("slow_rate", 0.0)?,
+            slow_us: number("slow_us", 0.0)? as u64,
+        })
+    }
+
+    /// The latency in effect as parameters of `parse`, or `none` if every response is immediate.
+    fn describe(&self) -> String {
+        let jitter_us = match self.jitter {
+            Jitter::None => 0,
+            Jitter::Uniform { max_us } => max_us,
+            Jitter::LogNormal { median_us, .. } => median_us,
+        };
+        if self.delay_us == 0 && jitter_us == 0 && (self.slow_rate == 0.0 || self.slow_us == 0) {
+            return "none".to_string();
+        }
+        let jitter = match self.jitter {
+            Jitter::None => "jitter=none".to_string(),
+            Jitter::Uniform { max_us } => format!("jitter=uniform&jitter_us={max_us}"),
+            Jitter::LogNormal { median_us, sigma } => format!("jitter=lognormal&jitter_us={median_us}&sigma={sigma}"),
+        };
+        format!("delay_us={}&{}&slow_rate={}&slow_us={}", self.delay_us, jitter, self.slow_rate, self.slow_us)
+    }
+
+    fn sample(&self) -> Duration {
+        let jitter_us = match self.jitter {
+            Jitter::None => 0.0,
+            Jitter::Uniform { max_us } => random() * max_us as f64,
+            Jitter::LogNormal { median_us, sigma } => {
+                // Box-Muller transform, 1 - random() is never 0.
+                let z = (-2.0 * (1.0 - random()).ln()).sqrt() * (2.0 * std::f64::consts::PI * random()).cos();
+                median_us as f64 * (sigma * z).exp()
+            }
+        };
+        let slow_us = match random() < self.slow_rate {
+            true => self.slow_us,
+            false => 0,
+        };
+        Duration::from_micros(self.delay_us + jitter_us as u64 + slow_us)
+    }
+}
+
+/// Waits for the latency of a data file.
+async fn wait() {
+    let delay = LATENCY.read().unwrap().sample();
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Uniformly distributed in [0, 1).
+fn random() -> f64 {
+    // splitmix64
+    let mut z = RANDOM_STATE.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The elements ordered by atomic number, which is the order the harness uses for the rotation.
+struct Data {
+    symbols: Vec<String>,
+    elements: Vec<Value>,
+    shells: Vec<Value>,
+
+    /// Files of the current version.
+    rendered: Mutex<Option<RenderedData>>,
+}
+
+/// `element.json` and `shells.json` of one version of a rotation.
+struct RenderedData {
+    seed: u64,
+    period_ms: u64,
+    version: u64,
+    element: Bytes,
+    shells: Bytes,
+}
+
+impl Data {
+    fn read() -> Data {
+        let element_json: Map<String, Value> = serde_json::from_str(&fs::read_to_string("static/element.json").unwrap()).unwrap();
+        let shells_json: Map<String, Value> = serde_json::from_str(&fs::read_to_string("static/shells.json").unwrap()).unwrap();
+        let mut entries: Vec<(String, Value)> = element_json.into_iter().collect();
+        entries.sort_by_key(|(_, element)| element["number"].as_u64().unwrap());
+        Data {
+            shells: entries.iter().map(|(symbol, _)| shells_json[symbol].clone()).collect(),
+            symbols: entries.iter().map(|(symbol, _)| symbol.clone()).collect(),
+            elements: entries.into_iter().map(|(_, element)| element).collect(),
+            rendered: Mutex::new(None),
+        }
+    }
+
+    /// The files of the current version if the rotation is enabled.
+    /// In every version, the names and shells are permuted between the elements,
+    /// so a response only matches the data of the second it was fetched in.
+    fn rotated(&self) -> Option<(Bytes, Bytes)> {
+        let period_ms = ROTATION_PERIOD_MS.load(Ordering::Relaxed);
+        if period_ms == 0 {
+            return None;
+        }
+        let seed = ROTATION_SEED.load(Ordering::Relaxed);
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let version = now_ms / period_ms;
+
+        let mut rendered = self.rendered.lock().unwrap();
+        if let Some(data) = rendered.as_ref() {
+            if (data.seed, data.period_ms, data.version) == (seed, period_ms, version) {
+                return Some((data.element.clone(), data.shells.clone()));
+            }
+        }
+
+        let permutation = permutation(seed, version, self.symbols.len());
+        let mut element_json = Map::new();
+        let mut shells_json = Map::new();
+        for (index, symbol) in self.symbols.iter().enumerate() {
+            let source = permutation[index];
+            let mut element = self.elements[index].clone();
+            element["name"] = self.elements[source]["name"].clone();
+            element_json.insert(symbol.clone(), element);
+            shells_json.insert(symbol.clone(), self.shells[source].clone());
+        }
+        let element = Bytes::from(serde_json::to_string(&element_json).unwrap());
+        let shells = Bytes::from(serde_json::to_string(&shells_json).unwrap());
+        *rendered = Some(RenderedData { seed, period_ms, version, element: element.clone(), shells: shells.clone() });
+        Some((element, shells))
+    }
+}
+
+/// Must be the same as `permutation` in `src/utils/data_rotation.rs` of the harness.
+fn permutation(seed: u64, version: u64, len: usize) -> Vec<usize> {
+    let mut state = seed ^ version.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let mut next = || {
+        // splitmix64
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    let mut permutation: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        permutation.swap(i, j);
+    }
+    permutation
+}
@@ -1,38 +1,150 @@
 use crate::benchmark::benchmark::{run_benchmark, AdditionalData, IterationResult};
+use crate::benchmark::web::conformance::conform;
+use crate::benchmark::web::sweep::{
+    analyze_sweep, SweepLevel, DEFAULT_LATENCY_SLO, DEFAULT_SWEEP_CONCURRENCY,
+};
 use crate::utils::copy_files;
+use crate::utils::cpu_affinity::CpuSet;
+use crate::utils::data_rotation::DataRotation;
 use crate::utils::docker_stats::DockerStatsReader;
+use crate::utils::expectation::{Expectation, HeaderExpectation, RotatedExpectation};
 use crate::utils::http_load_tester::{
-    run_http_load_test, PendingValidationResponse, PreparedHttpRequest,
+    derive_seed, run_http_load_test, ConnectionMode, HttpLoadConfig, HttpLoadResult, LoadMode,
+    PendingValidationResponse, PreparedHttpRequest, Protocol,
 };
+use crate::utils::load_engine::Engine;
 use crate::utils::meta_data_parser::WebBenchmarkMetaData;
+use crate::utils::request_trace::{prepare_replay, read_trace};
 use crate::utils::result_reader::ExistingResult;
 use crate::utils::result_writer::write_result_to_file;
-use crate::utils::serialization::SerializedValue;
+use crate::utils::upstream_latency::LatencyProfile;
 use crate::utils::version_migrator::VersionMigrator;
 use indexmap::IndexMap;
 use serde::Deserialize;
+use serde_json::json;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fs;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
+pub mod conformance;
+mod sweep;
+
 const DEFAULT_CONCURRENCY: usize = 32;
 
+/// Values of the descriptor columns for result rows that were written before the column existed.
+pub(crate) const LEGACY_COLUMN_DEFAULTS: &[(&str, &str)] = &[
+    ("load_mode", "closed"),
+    ("route_mix", "uniform"),
+    ("protocol", "http1"),
+    ("connection_mode", "keep-alive"),
+    ("upstream_latency", "none"),
+    ("engine", "reqwest"),
+    ("load_cpuset", "all"),
+    ("container_cpuset", "all"),
+];
+
+const ROUTE_ELEMENT: &str = "element";
+const ROUTE_SHELLS: &str = "shells";
+
+/// Command line options that override the values of `benchmark.yaml`.
+#[derive(Default)]
+pub struct WebBenchmarkOptions {
+    pub load_mode: Option<LoadMode>,
+    pub target_rps: Option<usize>,
+
+    /// Run the load test at multiple concurrency levels instead of one.
+    pub sweep: bool,
+    pub latency_slo: Option<Duration>,
+    pub error_budget: Option<f64>,
+
+    /// Base seed of the request order. A random seed is used if not set.
+    pub seed: Option<u64>,
+    pub protocol: Option<Protocol>,
+    pub connection_mode: Option<ConnectionMode>,
+    pub connections: Option<usize>,
+    pub max_in_flight: Option<usize>,
+    pub engine: Option<Engine>,
+
+    /// Worker threads of the load generator. Defaults to the size of `load_cpuset` or all cores.
+    pub load_threads: Option<usize>,
+    pub load_cpuset: Option<CpuSet>,
+
+    /// Cores of the benchmarked container, disjoint from `load_cpuset`.
+    pub container_cpuset: Option<CpuSet>,
+
+    /// JSONL trace that is replayed instead of the generated requests.
+    pub replay: Option<String>,
+
+    /// Factor to speed up (> 1.0) or slow down (< 1.0) the replay.
+    pub replay_speed: Option<f64>,
+
+    /// Write every load test to a trace in `result/traces/`.
+    pub record: bool,
+
+    /// Probe the edge cases first and skip the benchmark if a required check fails.
+    pub require_conformance: bool,
+
+    /// Period of the data rotation of the data source. No rotation if `None`.
+    pub data_rotation: Option<Duration>,
+    pub upstream_latency: Option<LatencyProfile>,
+}
+
+impl WebBenchmarkOptions {
+    pub fn load_threads(&self) -> usize {
+        self.load_threads
+            .or(self.load_cpuset.as_ref().map(CpuSet::count))
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
 pub fn benchmark_web(
     dir: &str,
     existing: Option<&ExistingResult>,
     stats_reader: &mut DockerStatsReader,
     validate: bool,
     verbose: bool,
+    options: &WebBenchmarkOptions,
 ) {
     let meta_data: WebBenchmarkMetaData = WebBenchmarkMetaData::read_from_directory(dir)
-        .expect(&format!("Failed to read meta data: {dir}"));
+        .unwrap_or_else(|_| panic!("Failed to read meta data: {dir}"));
+
+    let load_mode = options
+        .load_mode
+        .or(meta_data.load_mode)
+        .unwrap_or(LoadMode::Closed);
+    let protocol = options
+        .protocol
+        .or(meta_data.protocol)
+        .unwrap_or(Protocol::Http1);
+    let connection_mode = options
+        .connection_mode
+        .or(meta_data.connection_mode)
+        .unwrap_or(ConnectionMode::KeepAlive);
+    let upstream_latency = options
+        .upstream_latency
+        .or(meta_data.upstream_latency)
+        .unwrap_or(LatencyProfile::None);
+    let engine = options.engine.unwrap_or(Engine::Reqwest);
+
+    // Results of another configuration do not count as existing
+    let configuration = [
+        ("load_mode", load_mode.to_string()),
+        ("protocol", protocol.to_string()),
+        ("connection_mode", connection_mode.to_string()),
+        ("upstream_latency", upstream_latency.to_string()),
+        ("engine", engine.to_string()),
+    ];
 
     // Early check if all existing results are in metadata to avoid printing metadata info
     if let Some(existing) = existing {
         if meta_data.language_version.iter().all(|lang_version| {
             meta_data.framework_version.iter().all(|framework_version| {
-                existing.language_versions.contains(lang_version)
-                    && existing.framework_versions.contains(framework_version)
+                existing.contains_run(lang_version, framework_version, &configuration)
             })
         }) {
             println!(" -> Skipping {dir}");
@@ -43,7 +155,61 @@ pub fn benchmark_web(
     println!(" -> Benchmarking {dir}");
     meta_data.print_info();
 
+    if options.require_conformance
+        && !conform(dir, options.container_cpuset.as_ref()).passed_required()
+    {
+        panic!("Required conformance checks failed for {dir}");
+    }
+
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!(" -> Using seed = {}", seed);
+
+    let data_rotation = options
+        .data_rotation
+        .map(|period| DataRotation { seed, period });
+    if let Some(data_rotation) = &data_rotation {
+        if data_rotation.period.is_zero() {
+            panic!("The period of the data rotation must be positive");
+        }
+        if options.replay.is_some() {
+            panic!("--data-rotation cannot be combined with --replay");
+        }
+        println!(
+            " -> Rotating the data source every {} ms",
+            data_rotation.period.as_millis()
+        );
+    }
+
+    if upstream_latency != LatencyProfile::None {
+        println!(" -> Upstream latency: {}", upstream_latency);
+    }
+
+    let expected_headers = expected_headers();
     let data: HashMap<String, PeriodicTableElement> = load_data();
+
+    // With a data rotation, the name and the shells depend on the time of the request.
+    // The rotation orders the elements by atomic number.
+    let mut by_number: Vec<&PeriodicTableElement> = data.values().collect();
+    by_number.sort_by_key(|element| element.number);
+    let rotation_index = |element: &PeriodicTableElement| {
+        by_number
+            .iter()
+            .position(|other| other.number == element.number)
+            .unwrap()
+    };
+    let name_variants = Arc::new(
+        by_number
+            .iter()
+            .map(|element| Expectation::from(json!({ "name": element.name })))
+            .collect::<Vec<Expectation>>(),
+    );
+    let shells_variants = Arc::new(
+        by_number
+            .iter()
+            .map(|element| shells_expectation(element))
+            .collect::<Vec<Expectation>>(),
+    );
+
     let requests: Vec<PreparedHttpRequest> = [
         data.iter()
             .map(|(k, v)| {
@@ -51,24 +217,24 @@ pub fn benchmark_web(
                     "http://localhost:3000/api/v1/periodic-table/element?symbol={}",
                     k
                 );
-                let expected_response = HashMap::from([
-                    (
-                        "name".to_string(),
-                        SerializedValue::StringValue(v.name.to_string()),
-                    ),
-                    (
-                        "number".to_string(),
-                        SerializedValue::IntValue(v.number as i32),
-                    ),
-                    (
-                        "group".to_string(),
-                        SerializedValue::IntValue(v.group as i32),
+                let (expected_response, rotated_response) = match data_rotation {
+                    Some(rotation) => (
+                        Expectation::from(json!({ "number": v.number, "group": v.group })),
+                        Some(RotatedExpectation {
+                            rotation,
+                            index: rotation_index(v),
+                            variants: Arc::clone(&name_variants),
+                        }),
                     ),
-                ]);
-
+                    None => (element_expectation(v), None),
+                };
                 PreparedHttpRequest {
+                    route: ROUTE_ELEMENT.to_string(),
                     url,
-                    expected_response,
+                    expected_response: Some(expected_response),
+                    rotated_response,
+                    expected_headers: expected_headers.clone(),
+                    ..Default::default()
                 }
             })
             .collect::<Vec<PreparedHttpRequest>>(),
@@ -78,39 +244,241 @@ pub fn benchmark_web(
                     "http://localhost:3000/api/v1/periodic-table/shells?symbol={}",
                     k
                 );
-                let expected_response = HashMap::from([(
-                    "shells".to_string(),
-                    SerializedValue::IntListValue(
-                        v.shells.iter().map(|v| *v as i32).collect::<Vec<i32>>(),
+                let (expected_response, rotated_response) = match data_rotation {
+                    Some(rotation) => (
+                        None,
+                        Some(RotatedExpectation {
+                            rotation,
+                            index: rotation_index(v),
+                            variants: Arc::clone(&shells_variants),
+                        }),
                     ),
-                )]);
-
+                    None => (Some(shells_expectation(v)), None),
+                };
                 PreparedHttpRequest {
+                    route: ROUTE_SHELLS.to_string(),
                     url,
                     expected_response,
+                    rotated_response,
+                    expected_headers: expected_headers.clone(),
+                    ..Default::default()
                 }
             })
             .collect::<Vec<PreparedHttpRequest>>(),
     ]
     .concat();
 
-    let concurrency = match meta_data.concurrency {
-        Some(concurrency) => {
+    // A replay sends the requests of the trace instead of the generated ones.
+    let replay_speed = options.replay_speed.unwrap_or(1.0);
+    let (requests, schedule) = match &options.replay {
+        Some(path) => {
+            let replay = prepare_replay(&read_trace(path), "http://localhost:3000", replay_speed);
             println!(
-                " -> Using concurrency = {} instead of default = {}",
-                concurrency, DEFAULT_CONCURRENCY
+                " -> Replaying {} requests from {} at {}x speed ({} s)",
+                replay.requests.len(),
+                path,
+                replay_speed,
+                replay.schedule.last().unwrap().as_secs()
             );
+            (replay.requests, Some(Arc::new(replay.schedule)))
+        }
+        None => (requests, None),
+    };
+
+    let routes: Vec<String> = requests.iter().fold(Vec::new(), |mut routes, request| {
+        if !routes.contains(&request.route) {
+            routes.push(request.route.clone());
+        }
+        routes
+    });
+
+    let fixed_connections = options.connections.or(meta_data.connections);
+
+    // The concurrency is the maximum number of requests in flight.
+    let concurrency = match (
+        options.max_in_flight,
+        protocol,
+        fixed_connections,
+        meta_data.streams_per_connection,
+    ) {
+        (Some(max_in_flight), _, _, _) => max_in_flight,
+        (None, Protocol::H2c, Some(connections), Some(streams)) => connections * streams,
+        _ => match meta_data.concurrency {
+            Some(concurrency) => {
+                println!(
+                    " -> Using concurrency = {} instead of default = {}",
+                    concurrency, DEFAULT_CONCURRENCY
+                );
+                concurrency
+            }
+            None => DEFAULT_CONCURRENCY,
+        },
+    };
+
+    // By default, every request in flight has its own HTTP/1.1 connection,
+    // while HTTP/2 streams are spread over as few connections as configured.
+    let connections = |concurrency: usize| match fixed_connections {
+        Some(connections) => connections,
+        None => match protocol {
+            Protocol::Http1 => concurrency,
+            Protocol::H2c => match meta_data.streams_per_connection {
+                Some(streams) => concurrency.div_ceil(streams),
+                None => 1,
+            },
+        },
+    };
+    if connection_mode == ConnectionMode::Close {
+        if protocol == Protocol::H2c {
+            panic!("Connection mode close is only supported with http1");
+        }
+        println!(" -> Using a new connection per request");
+    }
+
+    if !engine.supports(protocol) {
+        panic!("The {} engine does not support {}", engine, protocol);
+    }
+    if engine != Engine::Reqwest {
+        println!(" -> Using the {} engine", engine);
+    }
+
+    let load_threads = options.load_threads();
+    let load_cpuset = cpuset_label(options.load_cpuset.as_ref());
+    let container_cpuset = cpuset_label(options.container_cpuset.as_ref());
+    println!(
+        " -> Load generator: {} threads on cpuset {}, container on cpuset {}",
+        load_threads, load_cpuset, container_cpuset
+    );
+
+    if protocol == Protocol::H2c {
+        println!(
+            " -> Using h2c with {} connections and {} streams in total",
+            connections(concurrency),
+            concurrency
+        );
+    } else if fixed_connections.is_some() {
+        println!(
+            " -> Using {} connections for {} requests in flight",
+            connections(concurrency),
             concurrency
+        );
+    }
+
+    let target_rps = options.target_rps.or(meta_data.target_rps);
+    if target_rps == Some(0) {
+        panic!("target_rps must be positive (benchmark.yaml or --target-rps)");
+    }
+    if load_mode == LoadMode::Open {
+        match target_rps {
+            Some(target_rps) => println!(" -> Using open-loop load mode with {target_rps} rps"),
+            None => {
+                panic!("Open-loop load mode requires target_rps (benchmark.yaml or --target-rps)")
+            }
         }
-        None => DEFAULT_CONCURRENCY,
+    }
+
+    if schedule.is_some() && options.sweep {
+        panic!("--sweep cannot be combined with --replay");
+    }
+    let sweep_concurrency: Option<Vec<usize>> = match options.sweep {
+        true => Some(
+            meta_data
+                .sweep_concurrency
+                .clone()
+                .unwrap_or(DEFAULT_SWEEP_CONCURRENCY.to_vec()),
+        ),
+        false => None,
     };
+    let latency_slo = options
+        .latency_slo
+        .or(meta_data.latency_slo.map(Duration::from_millis))
+        .unwrap_or(DEFAULT_LATENCY_SLO);
+    if let Some(levels) = &sweep_concurrency {
+        println!(
+            " -> Sweeping concurrency {:?} (p99 SLO = {} ms)",
+            levels,
+            latency_slo.as_millis()
+        );
+    }
+
+    let error_budget = options
+        .error_budget
+        .or(meta_data.error_budget)
+        .unwrap_or(0.0);
+    if !(0.0..=1.0).contains(&error_budget) {
+        panic!("error_budget must be between 0.0 and 1.0, got {error_budget}");
+    }
+    if error_budget > 0.0 {
+        println!(" -> Using error budget = {}", error_budget);
+    }
+
+    let route_mix = match (&options.replay, &meta_data.route_weights) {
+        (Some(path), _) => format!("replay:{}@{}x", replay_label(path), replay_speed),
+        (None, Some(route_weights)) => {
+            let route_mix = route_weights
+                .iter()
+                .map(|(route, weight)| format!("{route}={weight}"))
+                .collect::<Vec<String>>()
+                .join(";");
+            println!(" -> Using route mix {}", route_mix);
+            route_mix
+        }
+        (None, None) => "uniform".to_string(),
+    };
+
+    // A replay lasts as long as the trace, so there is no ramp-up.
+    let (ramp_up, exclude_start, exclude_end) = match schedule {
+        Some(_) => (Duration::ZERO, Duration::ZERO, Duration::ZERO),
+        None => (
+            Duration::from_secs(meta_data.ramp_up.unwrap_or(0)),
+            Duration::from_secs(meta_data.exclude_start.unwrap_or(0)),
+            Duration::from_secs(meta_data.exclude_end.unwrap_or(0)),
+        ),
+    };
+    let duration = match (&schedule, validate) {
+        (Some(schedule), _) => Duration::from_secs(schedule.last().unwrap().as_secs() + 2),
+        (None, true) => Duration::from_secs(2),
+        (None, false) => Duration::from_secs(15),
+    };
+
+    let load_config =
+        |concurrency: usize, iteration: u64, trace_path: Option<String>| HttpLoadConfig {
+            max_in_flight: concurrency,
+            protocol,
+            connections: connections(concurrency),
+            connection_mode,
+            engine,
+            worker_threads: load_threads,
+            cpuset: options.load_cpuset.clone(),
+            duration,
+            ramp_up,
+            exclude_start,
+            exclude_end,
+            load_mode,
+            target_rps,
+            validation: meta_data.validation.clone(),
+            route_weights: meta_data.route_weights.clone(),
+            schedule: schedule.clone(),
+            trace_path,
+            error_budget,
+            seed: derive_seed(seed, iteration),
+            base_seed: seed,
+            iteration,
+            verbose,
+        };
+
+    let measurement_window = load_config(concurrency, 0, None).measurement_window();
+    if !measurement_window.start.is_zero() {
+        println!(
+            " -> Using ramp-up = {} s, measurement window = {}",
+            ramp_up.as_secs(),
+            measurement_window
+        );
+    }
 
     for language_version in &meta_data.language_version {
         for framework_version in &meta_data.framework_version {
             if let Some(existing) = existing {
-                if existing.language_versions.contains(language_version)
-                    && existing.framework_versions.contains(framework_version)
-                {
+                if existing.contains_run(language_version, framework_version, &configuration) {
                     println!(
                         " -> Skipping {} v{} / {} v{} (already exists)",
                         meta_data.mode, language_version, meta_data.framework, framework_version
@@ -120,7 +488,7 @@ pub fn benchmark_web(
             }
 
             if let Some(copy_files) = &meta_data.copy {
-                copy_files::copy_files(dir, &copy_files);
+                copy_files::copy_files(dir, copy_files);
             }
 
             let mut version_migrations = Vec::with_capacity(2);
@@ -144,10 +512,23 @@ pub fn benchmark_web(
                 ));
             }
 
+            let headers_dir = format!(
+                "result/web_response_headers/{}",
+                dir.replace("benchmark/web/", "")
+            );
+
+            // Every iteration (including warmup and retries) gets its own seed derived from the base seed,
+            // so running again with the same --seed repeats the request order of each iteration.
+            let iteration = Cell::new(0);
+
+            // The data source reports the latency it actually adds, which includes its environment variables.
+            let effective_latency = RefCell::new("none".to_string());
+
             #[rustfmt::skip]
             let result = run_benchmark(
                 dir,
                 stats_reader,
+                options.container_cpuset.as_ref(),
                 version_migrations.iter_mut().collect(),
                 match validate {
                     true => 0,
@@ -161,58 +542,176 @@ pub fn benchmark_web(
                     false => 5,
                 },
                 || {
-                    let _ = reqwest::blocking::get("http://localhost:3001/reset").expect("Failed to reset counter");
+                    let mut additional_data: IndexMap<String, AdditionalData> = IndexMap::new();
+                    let mut debugging_data: IndexMap<String, AdditionalData> = IndexMap::new();
+                    let mut latency_histogram = None;
 
-                    let result = run_http_load_test(
-                        concurrency,
-                        Duration::from_secs(match validate {
-                            true => 2,
-                            false => 15,
-                        }),
-                        &requests,
-                        response_validator,
-                        verbose,
-                    );
+                    let iteration_index = iteration.get();
+                    iteration.set(iteration_index + 1);
+                    println!(" -> Seed: {}, iteration: {}", seed, iteration_index);
 
-                    if let Ok(response) = reqwest::blocking::get("http://localhost:3001/reset") {
-                        let data_source_counter = response.text().expect("Failed to read counter").parse::<i32>().expect("Failed to parse counter");
-                        if data_source_counter < result.success_count {
-                            // Note: data_source_counter might be bigger when some requests are timed out, which is fine
-                            panic!("Request count measured by data source: {}.
-Successful responses by framework: {}.
-Maybe some requests were not fired but cached responses were used?",
-                                data_source_counter, result.success_count);
+                    // Every load test is recorded to its own file.
+                    let trace_path = |suffix: &str| {
+                        let path = format!("result/traces/{}/{language_version}_{framework_version}_{iteration_index}{suffix}.jsonl", dir.replace("benchmark/web/", ""));
+                        options.record.then(|| {
+                            println!(" -> Recording to {}", path);
+                            path
+                        })
+                    };
+
+                    match &sweep_concurrency {
+                        None => {
+                            let (result, stats) = run_counted_load_test(&load_config(concurrency, iteration_index, trace_path("")), &requests, data_rotation.as_ref(), upstream_latency);
+                            write_response_headers(&format!("{headers_dir}/{language_version}_{framework_version}.json"), &result);
+                            insert_load_result(&mut additional_data, &mut debugging_data, &result, "");
+                            insert_data_source_stats(&mut debugging_data, &stats, "");
+                            effective_latency.replace(stats.latency);
+                            // External load tools do not provide a histogram.
+                            if !result.latency_histogram.is_empty() {
+                                latency_histogram = Some(result.latency_histogram);
+                            }
+                        }
+                        Some(levels) => {
+                            for level in levels {
+                                println!(" -> [Sweep]: concurrency = {}", level);
+                                let (result, stats) = run_counted_load_test(&load_config(*level, iteration_index, trace_path(&format!("_c{level}"))), &requests, data_rotation.as_ref(), upstream_latency);
+                                write_response_headers(&format!("{headers_dir}/{language_version}_{framework_version}_c{level}.json"), &result);
+                                insert_load_result(&mut additional_data, &mut debugging_data, &result, &format!("@{level}"));
+                                insert_data_source_stats(&mut debugging_data, &stats, &format!("@{level}"));
+                                effective_latency.replace(stats.latency);
+                            }
                         }
-                    } else {
-                        panic!("Failed to reset counter");
                     }
 
-                    let mut additional_data: IndexMap<String, AdditionalData> = IndexMap::new();
-                    additional_data.insert("rps_median".to_string(), AdditionalData::Int(result.rps_median));
-                    additional_data.insert("rps_p99".to_string(), AdditionalData::Int(result.rps_p99));
-                    additional_data.insert("latency_median".to_string(), AdditionalData::Int(result.latency_median.as_micros() as i32));
-                    additional_data.insert("latency_p99".to_string(), AdditionalData::Int(result.latency_p99.as_micros() as i32));
-                    additional_data.insert("errors".to_string(), AdditionalData::Int(result.fail_count));
-
-                    let mut debugging_data: IndexMap<String, AdditionalData> = IndexMap::new();
-                    debugging_data.insert("success".to_string(), AdditionalData::Int(result.success_count));
-                    debugging_data.insert("time".to_string(), AdditionalData::Int(result.total_time.as_millis() as i32));
-
                     Ok(IterationResult {
                         additional_data,
                         debugging_data,
+                        latency_histogram,
                     })
                 },
             );
 
             if let Some(copy_files) = &meta_data.copy {
-                copy_files::delete_copied_files(dir, &copy_files);
+                copy_files::delete_copied_files(dir, copy_files);
             }
 
             if validate {
                 continue;
             }
 
+            let upstream_latency = upstream_latency.label(&effective_latency.borrow());
+            if upstream_latency.starts_with("env:") {
+                println!(
+                    " -> Upstream latency from the environment of the data source: {}",
+                    upstream_latency
+                );
+            }
+
+            if let Some(levels) = &sweep_concurrency {
+                let mut sweep_levels: Vec<SweepLevel> = Vec::with_capacity(levels.len());
+                for level in levels {
+                    let get = |key: &str| {
+                        result
+                            .additional_data
+                            .get(&format!("{key}@{level}"))
+                            .unwrap()
+                            .to_string()
+                    };
+
+                    #[rustfmt::skip]
+                    write_result_to_file(
+                        "result/web_sweep_result.csv",
+                        &Vec::from([
+                            ("language", meta_data.language.as_str()),
+                            ("mode", meta_data.mode.as_str()),
+                            ("version", language_version.as_str()),
+                            ("framework", meta_data.framework.as_str()),
+                            ("framework_stdlib", meta_data.framework_stdlib.to_string().as_str()),
+                            ("framework_website", meta_data.framework_website.as_str()),
+                            ("framework_flavor", meta_data.framework_flavor.as_str()),
+                            ("framework_version", framework_version.as_str()),
+                            ("concurrency", level.to_string().as_str()),
+                            ("path", dir.replace("benchmark/web/", "").as_str()),
+                            ("load_mode", load_mode.to_string().as_str()),
+                            ("target_rps", target_rps.map(|rps| rps.to_string()).unwrap_or_default().as_str()),
+                            ("route_mix", route_mix.as_str()),
+                            ("protocol", protocol.to_string().as_str()),
+                            ("connection_mode", connection_mode.to_string().as_str()),
+                            ("upstream_latency", upstream_latency.as_str()),
+                            ("engine", engine.to_string().as_str()),
+                            ("load_threads", load_threads.to_string().as_str()),
+                            ("load_cpuset", load_cpuset.as_str()),
+                            ("container_cpuset", container_cpuset.as_str()),
+                        ]),
+                        &Vec::from([
+                            ("rps_median", get("rps_median").as_str()),
+                            ("rps_p99", get("rps_p99").as_str()),
+                            ("latency_median", get("latency_median").as_str()),
+                            ("latency_p99", get("latency_p99").as_str()),
+                            ("latency_p999", get("latency_p999").as_str()),
+                            ("achieved_rps", get("achieved_rps").as_str()),
+                            ("errors", get("errors").as_str()),
+                            ("seed", seed.to_string().as_str()),
+                            ("connections", get("connections").as_str()),
+                            ("peak_in_flight", get("peak_in_flight").as_str()),
+                            ("ttfb_median", get("ttfb_median").as_str()),
+                            ("ttfb_p99", get("ttfb_p99").as_str()),
+                        ]),
+                        LEGACY_COLUMN_DEFAULTS,
+                        take_bigger_rps,
+                    )
+                    .expect("Failed to write result to file");
+
+                    sweep_levels.push(SweepLevel {
+                        concurrency: *level,
+                        rps_median: get("rps_median").parse().unwrap(),
+                        latency_p99: Duration::from_micros(get("latency_p99").parse().unwrap()),
+                    });
+                }
+
+                let summary = analyze_sweep(&sweep_levels, latency_slo);
+
+                #[rustfmt::skip]
+                write_result_to_file(
+                    "result/web_sweep_summary.csv",
+                    &Vec::from([
+                        ("language", meta_data.language.as_str()),
+                        ("mode", meta_data.mode.as_str()),
+                        ("version", language_version.as_str()),
+                        ("framework", meta_data.framework.as_str()),
+                        ("framework_stdlib", meta_data.framework_stdlib.to_string().as_str()),
+                        ("framework_website", meta_data.framework_website.as_str()),
+                        ("framework_flavor", meta_data.framework_flavor.as_str()),
+                        ("framework_version", framework_version.as_str()),
+                        ("latency_slo", latency_slo.as_millis().to_string().as_str()),
+                        ("path", dir.replace("benchmark/web/", "").as_str()),
+                        ("load_mode", load_mode.to_string().as_str()),
+                        ("target_rps", target_rps.map(|rps| rps.to_string()).unwrap_or_default().as_str()),
+                        ("route_mix", route_mix.as_str()),
+                        ("protocol", protocol.to_string().as_str()),
+                        ("connection_mode", connection_mode.to_string().as_str()),
+                        ("upstream_latency", upstream_latency.as_str()),
+                        ("engine", engine.to_string().as_str()),
+                        ("load_threads", load_threads.to_string().as_str()),
+                        ("load_cpuset", load_cpuset.as_str()),
+                        ("container_cpuset", container_cpuset.as_str()),
+                    ]),
+                    &Vec::from([
+                        ("max_sustainable_rps", summary.max_sustainable_rps.map(|rps| rps.to_string()).unwrap_or_default().as_str()),
+                        ("knee_concurrency", summary.knee_concurrency.to_string().as_str()),
+                        ("memory_median", result.memory_median.to_string().as_str()),
+                        ("memory_p99", result.memory_p99.to_string().as_str()),
+                    ]),
+                    LEGACY_COLUMN_DEFAULTS,
+                    take_bigger_rps,
+                )
+                .expect("Failed to write result to file");
+                continue;
+            }
+
+            // The rows of the routes follow the row of web_result.csv, so that both describe the same run.
+            let kept_old_values = Cell::new(false);
+
             #[rustfmt::skip]
             write_result_to_file(
                 "result/web_result.csv",
@@ -227,6 +726,16 @@ Maybe some requests were not fired but cached responses were used?",
                     ("framework_version", framework_version.as_str()),
                     ("concurrency", concurrency.to_string().as_str()),
                     ("path", dir.replace("benchmark/web/", "").as_str()),
+                    ("load_mode", load_mode.to_string().as_str()),
+                    ("target_rps", target_rps.map(|rps| rps.to_string()).unwrap_or_default().as_str()),
+                    ("route_mix", route_mix.as_str()),
+                    ("protocol", protocol.to_string().as_str()),
+                    ("connection_mode", connection_mode.to_string().as_str()),
+                    ("upstream_latency", upstream_latency.as_str()),
+                    ("engine", engine.to_string().as_str()),
+                    ("load_threads", load_threads.to_string().as_str()),
+                    ("load_cpuset", load_cpuset.as_str()),
+                    ("container_cpuset", container_cpuset.as_str()),
                 ]),
                 &Vec::from([
                     ("rps_median", result.additional_data.get("rps_median").unwrap().to_string().as_str()),
@@ -236,12 +745,324 @@ Maybe some requests were not fired but cached responses were used?",
                     ("memory_median", result.memory_median.to_string().as_str()),
                     ("memory_p99", result.memory_p99.to_string().as_str()),
                     ("errors", result.additional_data.get("errors").unwrap().to_string().as_str()),
+                    ("latency_p999", result.additional_data.get("latency_p999").unwrap().to_string().as_str()),
+                    ("achieved_rps", result.additional_data.get("achieved_rps").unwrap().to_string().as_str()),
+                    ("latency_p90", result.additional_data.get("latency_p90").unwrap().to_string().as_str()),
+                    ("latency_max", result.additional_data.get("latency_max").unwrap().to_string().as_str()),
+                    ("errors_timeout", result.additional_data.get("errors_timeout").unwrap().to_string().as_str()),
+                    ("errors_refused", result.additional_data.get("errors_refused").unwrap().to_string().as_str()),
+                    ("errors_reset", result.additional_data.get("errors_reset").unwrap().to_string().as_str()),
+                    ("errors_status", result.additional_data.get("errors_status").unwrap().to_string().as_str()),
+                    ("errors_invalid", result.additional_data.get("errors_invalid").unwrap().to_string().as_str()),
+                    ("errors_other", result.additional_data.get("errors_other").unwrap().to_string().as_str()),
+                    ("ramp_up", ramp_up.as_secs().to_string().as_str()),
+                    ("measurement_window", measurement_window.to_string().as_str()),
+                    ("seed", seed.to_string().as_str()),
+                    ("connections", result.additional_data.get("connections").unwrap().to_string().as_str()),
+                    ("peak_in_flight", result.additional_data.get("peak_in_flight").unwrap().to_string().as_str()),
+                    ("ttfb_median", result.additional_data.get("ttfb_median").unwrap().to_string().as_str()),
+                    ("ttfb_p90", result.additional_data.get("ttfb_p90").unwrap().to_string().as_str()),
+                    ("ttfb_p99", result.additional_data.get("ttfb_p99").unwrap().to_string().as_str()),
+                    ("ttfb_p999", result.additional_data.get("ttfb_p999").unwrap().to_string().as_str()),
+                    ("bytes_per_response", result.additional_data.get("bytes_per_response").unwrap().to_string().as_str()),
                 ]),
-                take_bigger_rps,
+                LEGACY_COLUMN_DEFAULTS,
+                |old_values, new_values| {
+                    let values = take_bigger_rps(old_values, new_values);
+                    kept_old_values.set(std::ptr::eq(values, old_values));
+                    values
+                },
             )
             .expect("Failed to write result to file");
+
+            for route in &routes {
+                // External load tools do not report the results per route.
+                if !result
+                    .additional_data
+                    .contains_key(&format!("success[{route}]"))
+                {
+                    continue;
+                }
+
+                let get = |key: &str| {
+                    result
+                        .additional_data
+                        .get(&format!("{key}[{route}]"))
+                        .unwrap()
+                        .to_string()
+                };
+
+                #[rustfmt::skip]
+                write_result_to_file(
+                    "result/web_route_result.csv",
+                    &Vec::from([
+                        ("language", meta_data.language.as_str()),
+                        ("mode", meta_data.mode.as_str()),
+                        ("version", language_version.as_str()),
+                        ("framework", meta_data.framework.as_str()),
+                        ("framework_stdlib", meta_data.framework_stdlib.to_string().as_str()),
+                        ("framework_website", meta_data.framework_website.as_str()),
+                        ("framework_flavor", meta_data.framework_flavor.as_str()),
+                        ("framework_version", framework_version.as_str()),
+                        ("concurrency", concurrency.to_string().as_str()),
+                        ("path", dir.replace("benchmark/web/", "").as_str()),
+                        ("load_mode", load_mode.to_string().as_str()),
+                        ("target_rps", target_rps.map(|rps| rps.to_string()).unwrap_or_default().as_str()),
+                        ("route_mix", route_mix.as_str()),
+                        ("protocol", protocol.to_string().as_str()),
+                        ("connection_mode", connection_mode.to_string().as_str()),
+                        ("upstream_latency", upstream_latency.as_str()),
+                        ("engine", engine.to_string().as_str()),
+                        ("load_threads", load_threads.to_string().as_str()),
+                        ("load_cpuset", load_cpuset.as_str()),
+                        ("container_cpuset", container_cpuset.as_str()),
+                        ("route", route.as_str()),
+                    ]),
+                    &Vec::from([
+                        ("rps_share", get("rps_share").as_str()),
+                        ("success", get("success").as_str()),
+                        ("errors", get("errors").as_str()),
+                        ("latency_median", get("latency_median").as_str()),
+                        ("latency_p90", get("latency_p90").as_str()),
+                        ("latency_p99", get("latency_p99").as_str()),
+                        ("latency_p999", get("latency_p999").as_str()),
+                        ("ttfb_median", get("ttfb_median").as_str()),
+                        ("ttfb_p99", get("ttfb_p99").as_str()),
+                        ("bytes_per_response", get("bytes_per_response").as_str()),
+                    ]),
+                    LEGACY_COLUMN_DEFAULTS,
+                    |old_values, new_values| match kept_old_values.get() {
+                        true => old_values,
+                        false => new_values,
+                    },
+                )
+                .expect("Failed to write result to file");
+            }
+
+            if let Some(histogram) = &result.latency_histogram {
+                let histogram_dir = format!(
+                    "result/web_latency_histogram/{}",
+                    dir.replace("benchmark/web/", "")
+                );
+                fs::create_dir_all(&histogram_dir).expect("Failed to create histogram directory");
+                fs::write(
+                    format!("{histogram_dir}/{language_version}_{framework_version}.json"),
+                    serde_json::to_string(histogram).expect("Failed to serialize histogram"),
+                )
+                .expect("Failed to write histogram");
+            }
+        }
+    }
+}
+
+fn element_expectation(element: &PeriodicTableElement) -> Expectation {
+    Expectation::from(json!({
+        "name": element.name,
+        "number": element.number,
+        "group": element.group,
+    }))
+}
+
+fn shells_expectation(element: &PeriodicTableElement) -> Expectation {
+    Expectation::from(json!({
+        "shells": element.shells,
+    }))
+}
+
+/// Every response must be JSON and must not be cacheable.
+/// A `Content-Length` instead of a chunked body is only recommended, see `conformance`.
+fn expected_headers() -> HeaderExpectation {
+    HeaderExpectation {
+        required: IndexMap::from([(
+            "content-type".to_string(),
+            Some("application/json".to_string()),
+        )]),
+        forbidden: vec!["age".to_string(), "expires".to_string()],
+    }
+}
+
+/// Stores the headers the framework has sent per route, overwritten by every iteration.
+fn write_response_headers(path: &str, result: &HttpLoadResult) {
+    let headers: IndexMap<&String, IndexMap<&String, &String>> = result
+        .routes
+        .iter()
+        .filter(|(_, route)| !route.headers.is_empty())
+        .map(|(name, route)| {
+            let headers = route.headers.iter().map(|(name, value)| (name, value));
+            (name, headers.collect())
+        })
+        .collect();
+    if headers.is_empty() {
+        return;
+    }
+    if let Some(parent_dir) = std::path::Path::new(path).parent() {
+        fs::create_dir_all(parent_dir).expect("Failed to create response headers directory");
+    }
+    fs::write(
+        path,
+        serde_json::to_string_pretty(&headers).expect("Failed to serialize response headers"),
+    )
+    .expect("Failed to write response headers");
+}
+
+/// Enables the rotation of the data source, or disables it if `None`.
+fn set_data_rotation(data_rotation: Option<&DataRotation>) {
+    let path = data_rotation.map_or("/rotation".to_string(), DataRotation::control_path);
+    let _ = reqwest::blocking::get(format!("http://localhost:3001{path}"))
+        .expect("Failed to set data rotation");
+}
+
+/// Requests and traffic the data source has served since the last reset.
+/// Only connections that fetched data are counted.
+#[derive(Deserialize)]
+struct DataSourceStats {
+    requests: IndexMap<String, i32>,
+    connections: i32,
+    peak_connections: i32,
+    bytes: u64,
+
+    /// Latency in effect, `none` or the parameters of `/latency`.
+    latency: String,
+}
+
+/// Sets the latency profile of the data source.
+fn set_upstream_latency(upstream_latency: LatencyProfile) {
+    let response = reqwest::blocking::get(format!(
+        "http://localhost:3001{}",
+        upstream_latency.control_path()
+    ))
+    .expect("Failed to set upstream latency");
+    if !response.status().is_success() {
+        panic!(
+            "Failed to set upstream latency: {}",
+            response.text().unwrap_or_default()
+        );
+    }
+}
+
+/// Runs a single load test and cross-checks the number of successful responses
+/// with the number of requests the data source has received.
+fn run_counted_load_test(
+    config: &HttpLoadConfig,
+    requests: &[PreparedHttpRequest],
+    data_rotation: Option<&DataRotation>,
+    upstream_latency: LatencyProfile,
+) -> (HttpLoadResult, DataSourceStats) {
+    let _ = reqwest::blocking::get("http://localhost:3001/reset").expect("Failed to reset counter");
+    set_data_rotation(data_rotation);
+    set_upstream_latency(upstream_latency);
+
+    let result = run_http_load_test(config, requests, response_validator);
+    if config.verbose {
+        println!(
+            " -> Connections: {}, max in flight: {}, peak in flight: {}",
+            result.connections, result.max_in_flight, result.peak_in_flight
+        );
+        println!(
+            " -> RPS per second ({}): {:?}",
+            result.measurement_window, result.rps_per_second
+        );
+        for (route, route_result) in &result.routes {
+            println!(
+                " -> Route {}: success = {}, errors = {}, share = {}%, latency p99 = {} us, ttfb p99 = {} us, {} bytes",
+                route,
+                route_result.success_count,
+                route_result.fail_count,
+                route_result.rps_share,
+                route_result.latency_p99.as_micros(),
+                route_result.ttfb_p99.as_micros(),
+                route_result.bytes_per_response
+            );
         }
     }
+
+    let stats: DataSourceStats = reqwest::blocking::get("http://localhost:3001/stats")
+        .and_then(|response| response.json())
+        .expect("Failed to read data source stats");
+    if config.verbose {
+        println!(
+            " -> Data source: requests = {:?}, connections = {}, peak connections = {}, {} bytes",
+            stats.requests, stats.connections, stats.peak_connections, stats.bytes
+        );
+    }
+
+    // Replayed requests do not necessarily fetch from the data source.
+    let data_source_counter: i32 = stats.requests.values().sum();
+    if config.schedule.is_none() && data_source_counter < result.success_count {
+        // Note: data_source_counter might be bigger when some requests are timed out, which is fine
+        panic!(
+            "Request count measured by data source: {}.
+Successful responses by framework: {}.
+Maybe some requests were not fired but cached responses were used?",
+            data_source_counter, result.success_count
+        );
+    }
+
+    (result, stats)
+}
+
+/// Inserts the metrics of `result` into the iteration maps.
+/// `suffix` is appended to every key to distinguish multiple load tests in one iteration.
+#[rustfmt::skip]
+fn insert_load_result(
+    additional_data: &mut IndexMap<String, AdditionalData>,
+    debugging_data: &mut IndexMap<String, AdditionalData>,
+    result: &HttpLoadResult,
+    suffix: &str,
+) {
+    additional_data.insert(format!("rps_median{suffix}"), AdditionalData::Int(result.rps_median));
+    additional_data.insert(format!("rps_p99{suffix}"), AdditionalData::Int(result.rps_p99));
+    additional_data.insert(format!("latency_median{suffix}"), AdditionalData::Int(result.latency_median.as_micros() as i32));
+    additional_data.insert(format!("latency_p90{suffix}"), AdditionalData::Int(result.latency_p90.as_micros() as i32));
+    additional_data.insert(format!("latency_p99{suffix}"), AdditionalData::Int(result.latency_p99.as_micros() as i32));
+    additional_data.insert(format!("latency_p999{suffix}"), AdditionalData::Int(result.latency_p999.as_micros() as i32));
+    additional_data.insert(format!("latency_max{suffix}"), AdditionalData::Int(result.latency_max.as_micros() as i32));
+    additional_data.insert(format!("ttfb_median{suffix}"), AdditionalData::Int(result.ttfb_median.as_micros() as i32));
+    additional_data.insert(format!("ttfb_p90{suffix}"), AdditionalData::Int(result.ttfb_p90.as_micros() as i32));
+    additional_data.insert(format!("ttfb_p99{suffix}"), AdditionalData::Int(result.ttfb_p99.as_micros() as i32));
+    additional_data.insert(format!("ttfb_p999{suffix}"), AdditionalData::Int(result.ttfb_p999.as_micros() as i32));
+    additional_data.insert(format!("bytes_per_response{suffix}"), AdditionalData::Int(result.bytes_per_response));
+    additional_data.insert(format!("achieved_rps{suffix}"), AdditionalData::Int(result.achieved_rps));
+    additional_data.insert(format!("errors{suffix}"), AdditionalData::Int(result.fail_count));
+    additional_data.insert(format!("errors_timeout{suffix}"), AdditionalData::Int(result.errors.timeout));
+    additional_data.insert(format!("errors_refused{suffix}"), AdditionalData::Int(result.errors.connection_refused));
+    additional_data.insert(format!("errors_reset{suffix}"), AdditionalData::Int(result.errors.connection_reset));
+    additional_data.insert(format!("errors_status{suffix}"), AdditionalData::Int(result.errors.status));
+    additional_data.insert(format!("errors_invalid{suffix}"), AdditionalData::Int(result.errors.invalid));
+    additional_data.insert(format!("errors_other{suffix}"), AdditionalData::Int(result.errors.other));
+    additional_data.insert(format!("connections{suffix}"), AdditionalData::Int(result.connections as i32));
+    additional_data.insert(format!("peak_in_flight{suffix}"), AdditionalData::Int(result.peak_in_flight as i32));
+
+    debugging_data.insert(format!("success{suffix}"), AdditionalData::Int(result.success_count));
+    debugging_data.insert(format!("time{suffix}"), AdditionalData::Int(result.total_time.as_millis() as i32));
+    for (route, route_result) in &result.routes {
+        additional_data.insert(format!("success[{route}]{suffix}"), AdditionalData::Int(route_result.success_count));
+        additional_data.insert(format!("errors[{route}]{suffix}"), AdditionalData::Int(route_result.fail_count));
+        additional_data.insert(format!("rps_share[{route}]{suffix}"), AdditionalData::Int(route_result.rps_share));
+        additional_data.insert(format!("latency_median[{route}]{suffix}"), AdditionalData::Int(route_result.latency_median.as_micros() as i32));
+        additional_data.insert(format!("latency_p90[{route}]{suffix}"), AdditionalData::Int(route_result.latency_p90.as_micros() as i32));
+        additional_data.insert(format!("latency_p99[{route}]{suffix}"), AdditionalData::Int(route_result.latency_p99.as_micros() as i32));
+        additional_data.insert(format!("latency_p999[{route}]{suffix}"), AdditionalData::Int(route_result.latency_p999.as_micros() as i32));
+        additional_data.insert(format!("ttfb_median[{route}]{suffix}"), AdditionalData::Int(route_result.ttfb_median.as_micros() as i32));
+        additional_data.insert(format!("ttfb_p99[{route}]{suffix}"), AdditionalData::Int(route_result.ttfb_p99.as_micros() as i32));
+        additional_data.insert(format!("bytes_per_response[{route}]{suffix}"), AdditionalData::Int(route_result.bytes_per_response));
+    }
+
+    if let Some(target_rps) = result.target_rps {
+        debugging_data.insert(format!("achieved_percent{suffix}"), AdditionalData::Int(result.achieved_rps * 100 / target_rps));
+    }
+}
+
+/// Inserts the data source stats into the debugging data.
+/// The number of upstream connections shows whether a framework pools its connections.
+#[rustfmt::skip]
+fn insert_data_source_stats(debugging_data: &mut IndexMap<String, AdditionalData>, stats: &DataSourceStats, suffix: &str) {
+    for (path, count) in &stats.requests {
+        debugging_data.insert(format!("upstream_requests[{path}]{suffix}"), AdditionalData::Int(*count));
+    }
+    debugging_data.insert(format!("upstream_connections{suffix}"), AdditionalData::Int(stats.connections));
+    debugging_data.insert(format!("upstream_peak_connections{suffix}"), AdditionalData::Int(stats.peak_connections));
+    debugging_data.insert(format!("upstream_kib{suffix}"), AdditionalData::Int((stats.bytes / 1024) as i32));
 }
 
 #[derive(Deserialize)]
@@ -252,6 +1073,18 @@ struct PeriodicTableElement {
     shells: Vec<u8>,
 }
 
+/// File stem of the replayed trace, without commas so that it fits into a CSV cell.
+fn replay_label(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or(path.into(), |stem| stem.to_string_lossy())
+        .replace(',', "_")
+}
+
+fn cpuset_label(cpuset: Option<&CpuSet>) -> String {
+    cpuset.map_or("all".to_string(), CpuSet::label)
+}
+
 fn load_data() -> HashMap<String, PeriodicTableElement> {
     let data: String = fs::read_to_string("src/benchmark/web/data/static/data.json").unwrap();
     let json: serde_json::Value = serde_json::from_str(data.as_str()).unwrap();
@@ -274,55 +1107,10 @@ fn response_validator(response: &PendingValidationResponse) -> Result<(), String
         }
     };
 
-    for (key, expected_value) in response.expected_body {
-        let actual_value = json.get(key).ok_or(&format!(
-            r#"Expected "{key}": {expected_value} but this key does not exist"#,
-        ))?;
-        match expected_value {
-            SerializedValue::StringValue(v) => {
-                if actual_value.as_str().ok_or(&format!(
-                    r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                ))? != v
-                {
-                    return Err(format!(
-                        r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                    ));
-                }
-            }
-            SerializedValue::IntValue(v) => {
-                if actual_value.as_i64().ok_or(&format!(
-                    r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                ))? != *v as i64
-                {
-                    return Err(format!(
-                        r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                    ));
-                }
-            }
-            SerializedValue::IntListValue(v) => {
-                let actual_list = actual_value.as_array().ok_or(&format!(
-                    r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                ))?;
-                if actual_list.len() != v.len() {
-                    return Err(format!(
-                        r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                    ));
-                }
-                for (i, actual_value) in actual_list.iter().enumerate() {
-                    if actual_value.as_i64().ok_or(&format!(
-                        r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                    ))? != v[i] as i64
-                    {
-                        return Err(format!(
-                            r#"Expected "{key}": {expected_value} but got <{actual_value:?}>"#,
-                        ));
-                    }
-                }
-            }
-        }
+    match response.expected_body {
+        Some(expected) => expected.check(&json),
+        None => Ok(()),
     }
-
-    Ok(())
 }
 
 fn take_bigger_rps<'a>(old_values: &'a [&'a str], new_values: &'a [&'a str]) -> &'a [&'a str] {
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+pub const DEFAULT_SWEEP_CONCURRENCY: [usize; 5] = [1, 4, 16, 64, 256];
+pub const DEFAULT_LATENCY_SLO: Duration = Duration::from_millis(100);
+
+/// Minimum relative RPS increase for a level to count as "still growing".
+const MIN_RPS_GAIN: f64 = 0.05;
+
+/// Median results of one concurrency level of a sweep.
+pub struct SweepLevel {
+    pub concurrency: usize,
+    pub rps_median: i32,
+    pub latency_p99: Duration,
+}
+
+pub struct SweepSummary {
+    /// The last concurrency level that still increased the throughput noticeably
+    /// without the p99 latency growing faster than the throughput.
+    pub knee_concurrency: usize,
+
+    /// The highest median RPS of all levels whose p99 latency is within the SLO.
+    /// `None` if no level meets the SLO.
+    pub max_sustainable_rps: Option<i32>,
+}
+
+/// Finds the saturation point of a sweep.
+/// `levels` must not be empty and must be sorted by concurrency in ascending order.
+pub fn analyze_sweep(levels: &[SweepLevel], latency_slo: Duration) -> SweepSummary {
+    let (first, rest) = levels
+        .split_first()
+        .expect("A sweep needs at least one level");
+    let mut knee = first;
+    for level in rest {
+        let rps_gain = level.rps_median as f64 / knee.rps_median.max(1) as f64;
+        // Below saturation, more concurrency increases the throughput at about the same latency.
+        // Above it, the throughput stays flat and the latency grows with the queue.
+        let latency_growth =
+            level.latency_p99.as_secs_f64() / knee.latency_p99.as_secs_f64().max(1e-6);
+        if rps_gain >= 1.0 + MIN_RPS_GAIN && latency_growth <= rps_gain {
+            knee = level;
+        } else {
+            break;
+        }
+    }
+
+    let max_sustainable_rps = levels
+        .iter()
+        .filter(|level| level.latency_p99 <= latency_slo)
+        .map(|level| level.rps_median)
+        .max();
+
+    SweepSummary {
+        knee_concurrency: knee.concurrency,
+        max_sustainable_rps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(concurrency: usize, rps_median: i32, latency_p99_ms: u64) -> SweepLevel {
+        SweepLevel {
+            concurrency,
+            rps_median,
+            latency_p99: Duration::from_millis(latency_p99_ms),
+        }
+    }
+
+    #[test]
+    fn should_find_knee_where_rps_stops_growing() {
+        let levels = vec![
+            level(1, 1000, 1),
+            level(4, 3500, 2),
+            level(16, 9000, 5),
+            level(64, 9200, 40),
+            level(256, 9100, 200),
+        ];
+        let summary = analyze_sweep(&levels, Duration::from_millis(100));
+        assert_eq!(summary.knee_concurrency, 16);
+        assert_eq!(summary.max_sustainable_rps, Some(9200));
+    }
+
+    #[test]
+    fn should_find_knee_where_latency_takes_off() {
+        let levels = vec![
+            level(1, 1000, 1),
+            level(4, 3000, 2),
+            level(16, 4000, 20),
+            level(64, 4500, 90),
+        ];
+        let summary = analyze_sweep(&levels, Duration::from_millis(100));
+        assert_eq!(summary.knee_concurrency, 4);
+    }
+
+    #[test]
+    fn should_use_last_level_if_rps_keeps_growing() {
+        let levels = vec![level(1, 1000, 1), level(4, 2000, 1), level(16, 4000, 2)];
+        let summary = analyze_sweep(&levels, Duration::from_millis(100));
+        assert_eq!(summary.knee_concurrency, 16);
+        assert_eq!(summary.max_sustainable_rps, Some(4000));
+    }
+
+    #[test]
+    fn should_ignore_levels_above_slo() {
+        let levels = vec![level(1, 1000, 20), level(4, 3000, 60), level(16, 5000, 150)];
+        let summary = analyze_sweep(&levels, Duration::from_millis(50));
+        assert_eq!(summary.max_sustainable_rps, Some(1000));
+    }
+
+    #[test]
+    fn should_return_none_if_no_level_meets_slo() {
+        let levels = vec![level(1, 1000, 20), level(4, 3000, 60)];
+        let summary = analyze_sweep(&levels, Duration::from_millis(10));
+        assert_eq!(summary.max_sustainable_rps, None);
+    }
+}
@@ -1,10 +1,15 @@
 extern crate core;
 
 use crate::benchmark::computation::benchmark_computation;
-use crate::benchmark::web::benchmark_web;
+use crate::benchmark::web::conformance::conform;
+use crate::benchmark::web::{benchmark_web, WebBenchmarkOptions};
+use crate::utils::cpu_affinity::CpuSet;
 use crate::utils::docker_runner::run_docker_compose;
 use crate::utils::docker_stats;
+use crate::utils::http_load_tester::{ConnectionMode, LoadMode, Protocol};
+use crate::utils::load_engine::Engine;
 use crate::utils::result_reader::{ExistingResult, ResultMap};
+use crate::utils::upstream_latency::LatencyProfile;
 use clap::Parser;
 use docker_stats::DockerStatsReader;
 use std::collections::HashMap;
@@ -51,6 +56,113 @@ struct Args {
     /// No results will be saved.
     #[arg(long)]
     validate: bool,
+
+    /// Load mode of the web benchmark.
+    /// Overrides `load_mode` in benchmark.yaml
+    #[arg(long, value_enum, value_name = "MODE")]
+    load_mode: Option<LoadMode>,
+
+    /// Target requests per second for the open-loop load mode.
+    /// Overrides `target_rps` in benchmark.yaml
+    #[arg(long, value_name = "RPS")]
+    target_rps: Option<usize>,
+
+    /// Run the web benchmark at multiple concurrency levels to find the saturation point.
+    /// Results are written to web_sweep_result.csv and web_sweep_summary.csv
+    #[arg(long)]
+    sweep: bool,
+
+    /// p99 latency SLO in milliseconds used by `--sweep`.
+    /// Overrides `latency_slo` in benchmark.yaml
+    #[arg(long, value_name = "MS")]
+    latency_slo: Option<u64>,
+
+    /// Fraction of requests that may fail in the web benchmark (e.g. 0.01 for 1%).
+    /// Overrides `error_budget` in benchmark.yaml
+    #[arg(long, value_name = "FRACTION")]
+    error_budget: Option<f64>,
+
+    /// Seed of the request order in the web benchmark.
+    /// Use the seed printed by a previous run to send the same requests again.
+    #[arg(long, value_name = "SEED")]
+    seed: Option<u64>,
+
+    /// Protocol of the web benchmark.
+    /// Overrides `protocol` in benchmark.yaml
+    #[arg(long, value_enum, value_name = "PROTOCOL")]
+    protocol: Option<Protocol>,
+
+    /// Whether the web benchmark reuses connections or opens one per request.
+    /// Overrides `connection_mode` in benchmark.yaml
+    #[arg(long, value_enum, value_name = "MODE")]
+    connection_mode: Option<ConnectionMode>,
+
+    /// Number of connections used by the web benchmark.
+    /// Overrides `connections` in benchmark.yaml
+    #[arg(long, value_name = "N")]
+    connections: Option<usize>,
+
+    /// Maximum number of requests in flight in the web benchmark.
+    /// Overrides `concurrency` in benchmark.yaml
+    #[arg(long, value_name = "N")]
+    max_in_flight: Option<usize>,
+
+    /// HTTP client used to generate the load of the web benchmark.
+    /// `wrk`, `oha` and `k6` run the locally installed tool instead of the built-in client
+    #[arg(long, value_enum, value_name = "ENGINE")]
+    engine: Option<Engine>,
+
+    /// Measure the maximum throughput of the load generator against an in-process no-op server and exit.
+    /// Uses the engine set by `--engine` or all engines.
+    #[arg(long)]
+    calibrate: bool,
+
+    /// Number of worker threads of the web load generator.
+    /// Defaults to the number of cores in `--load-cpuset` or all cores
+    #[arg(long, value_name = "N")]
+    load_threads: Option<usize>,
+
+    /// Pin the web load generator to these cores (Linux only), e.g. "2-3"
+    #[arg(long, value_name = "CPUS")]
+    load_cpuset: Option<CpuSet>,
+
+    /// Pin the benchmarked web container to these cores, e.g. "0-1".
+    /// Must not overlap with `--load-cpuset`
+    #[arg(long, value_name = "CPUS")]
+    container_cpuset: Option<CpuSet>,
+
+    /// Replay the requests of a JSONL trace at their recorded send times instead of the generated requests.
+    /// See README for the format
+    #[arg(long, value_name = "FILE")]
+    replay: Option<String>,
+
+    /// Speed factor of `--replay`, e.g. 2.0 to replay twice as fast
+    #[arg(long, value_name = "FACTOR")]
+    replay_speed: Option<f64>,
+
+    /// Write every sent request with its status, latency and validation outcome to result/traces/.
+    /// The bodies of failed responses and a sample of the successful ones are included
+    #[arg(long)]
+    record: bool,
+
+    /// Probe the edge cases of the API spec for a web benchmark and exit, e.g. "rust/axum-0.7-rust-1.86".
+    /// Fails if a required check fails
+    #[arg(long, value_name = "LANG/VARIANT")]
+    conform: Option<String>,
+
+    /// Run the conformance checks before each web benchmark and abort if a required check fails
+    #[arg(long)]
+    require_conformance: bool,
+
+    /// Let the web data source permute its data every <MS> milliseconds and check that every
+    /// response reflects the data of the time it was sent, which detects cached upstream responses
+    #[arg(long, value_name = "MS")]
+    data_rotation: Option<u64>,
+
+    /// Latency profile of the web data source, to simulate an upstream that waits like a database.
+    /// Overrides `upstream_latency` in benchmark.yaml
+    #[arg(long, value_enum, value_name = "PROFILE")]
+    upstream_latency: Option<LatencyProfile>,
 }
 
 const CONTAINER_NAME: &str = "benchmark";
@@ -59,6 +171,56 @@ const WEB_DATASOURCE_DIR: &str = "src/benchmark/web/data";
 fn main() {
     let mut args = Args::parse();
 
+    let web_options = WebBenchmarkOptions {
+        load_mode: args.load_mode,
+        target_rps: args.target_rps,
+        sweep: args.sweep,
+        latency_slo: args.latency_slo.map(Duration::from_millis),
+        error_budget: args.error_budget,
+        seed: args.seed,
+        protocol: args.protocol,
+        connection_mode: args.connection_mode,
+        connections: args.connections,
+        max_in_flight: args.max_in_flight,
+        engine: args.engine,
+        load_threads: args.load_threads,
+        load_cpuset: args.load_cpuset.clone(),
+        container_cpuset: args.container_cpuset.clone(),
+        replay: args.replay.clone(),
+        replay_speed: args.replay_speed,
+        record: args.record,
+        require_conformance: args.require_conformance,
+        data_rotation: args.data_rotation.map(Duration::from_millis),
+        upstream_latency: args.upstream_latency,
+    };
+
+    if let (Some(load_cpuset), Some(container_cpuset)) = (&args.load_cpuset, &args.container_cpuset)
+    {
+        if !load_cpuset.is_disjoint(container_cpuset) {
+            panic!(
+                "The cpusets of the load generator ({}) and the container ({}) overlap",
+                load_cpuset, container_cpuset
+            );
+        }
+    }
+
+    if args.calibrate {
+        benchmark::calibration::calibrate(&web_options, args.verbose);
+        return;
+    }
+
+    if let Some(dir) = &args.conform {
+        let full_dir = format!("benchmark/web/{}", dir);
+        let mut passed = false;
+        run_docker_compose(WEB_DATASOURCE_DIR, Duration::ZERO, None, || {
+            passed = conform(&full_dir, args.container_cpuset.as_ref()).passed_required();
+        });
+        if !passed {
+            panic!("Required conformance checks failed for {}", full_dir);
+        }
+        return;
+    }
+
     let mut reader = DockerStatsReader::new();
     reader.run(CONTAINER_NAME);
 
@@ -100,6 +262,7 @@ fn main() {
                     &mut reader,
                     args.validate,
                     args.verbose,
+                    &web_options,
                 );
             });
         } else {
@@ -134,7 +297,14 @@ fn main() {
                     |dir: &str,
                      existing: Option<&ExistingResult>,
                      reader: &mut DockerStatsReader| {
-                        benchmark_web(dir, existing, reader, args.validate, args.verbose)
+                        benchmark_web(
+                            dir,
+                            existing,
+                            reader,
+                            args.validate,
+                            args.verbose,
+                            &web_options,
+                        )
                     },
                 );
             });
@@ -173,7 +343,14 @@ fn main() {
                 &existing_results.web,
                 &mut reader,
                 |dir: &str, existing: Option<&ExistingResult>, reader: &mut DockerStatsReader| {
-                    benchmark_web(dir, existing, reader, args.validate, args.verbose)
+                    benchmark_web(
+                        dir,
+                        existing,
+                        reader,
+                        args.validate,
+                        args.verbose,
+                        &web_options,
+                    )
                 },
             );
         });
@@ -212,7 +389,7 @@ fn run_one_language<F>(
 ) where
     F: Fn(&str, Option<&ExistingResult>, &mut DockerStatsReader),
 {
-    let variants = fs::read_dir(dir).expect(&format!("Could not read directory {}", dir));
+    let variants = fs::read_dir(dir).unwrap_or_else(|_| panic!("Could not read directory {}", dir));
     for variant_folder in variants {
         let variant_folder = variant_folder.unwrap();
         if !variant_folder.file_type().unwrap().is_dir() {
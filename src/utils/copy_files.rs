@@ -46,12 +46,11 @@ fn delete_empty_folder(folder_path: &Path, work_dir: &str) {
                 fs::remove_dir(folder_path).expect("Failed to remove directory");
                 println!(
                     " -> Removed {}",
-                    folder_path.display().to_string().replace(work_dir, "")[1..].to_string()
+                    &folder_path.display().to_string().replace(work_dir, "")[1..]
                 );
 
                 let parent_path = Path::new(folder_path).parent().unwrap();
                 delete_empty_folder(parent_path, work_dir);
-                return;
             }
         }
         Err(_) => println!(" -> Error while reading directory {folder_path:?}"),
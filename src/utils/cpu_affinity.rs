@@ -0,0 +1,147 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Number of cores a `cpu_set_t` can hold, higher cores cannot be pinned.
+#[cfg(target_os = "linux")]
+const MAX_CPUS: usize = libc::CPU_SETSIZE as usize;
+#[cfg(not(target_os = "linux"))]
+const MAX_CPUS: usize = 1024;
+
+/// A set of CPU cores in the notation of Docker and taskset, e.g. "0-3,6".
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpuSet {
+    /// Sorted and without duplicates.
+    cpus: Vec<usize>,
+}
+
+impl CpuSet {
+    pub fn count(&self) -> usize {
+        self.cpus.len()
+    }
+
+    /// Like the notation of Docker, but with "+" instead of "," to fit into a CSV cell, e.g. "0-3+6".
+    pub fn label(&self) -> String {
+        self.to_string().replace(',', "+")
+    }
+
+    pub fn is_disjoint(&self, other: &CpuSet) -> bool {
+        self.cpus.iter().all(|cpu| !other.cpus.contains(cpu))
+    }
+
+    /// Restricts the calling thread to the cores of this set.
+    #[cfg(target_os = "linux")]
+    pub fn pin_current_thread(&self) {
+        // SAFETY: cpu_set_t is a plain bit mask, sched_setaffinity only reads it.
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for cpu in &self.cpus {
+                libc::CPU_SET(*cpu, &mut set);
+            }
+            if libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set) != 0 {
+                panic!(
+                    "Failed to pin thread to cpuset {}: {}",
+                    self,
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn pin_current_thread(&self) {
+        panic!("Pinning to a cpuset is only supported on Linux");
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CpuSet, String> {
+        let mut cpus: Vec<usize> = Vec::new();
+        for part in s.split(',') {
+            let parse = |cpu: &str| match cpu.trim().parse::<usize>() {
+                Ok(cpu) if cpu < MAX_CPUS => Ok(cpu),
+                Ok(cpu) => Err(format!(
+                    "Cpu {} in cpuset \"{}\" is out of range (0..{})",
+                    cpu, s, MAX_CPUS
+                )),
+                Err(_) => Err(format!("Invalid cpu \"{}\" in cpuset \"{}\"", cpu, s)),
+            };
+            match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(format!("Invalid range \"{}\" in cpuset \"{}\"", part, s));
+                    }
+                    cpus.extend(start..=end);
+                }
+                None => cpus.push(parse(part)?),
+            }
+        }
+        cpus.sort();
+        cpus.dedup();
+        Ok(CpuSet { cpus })
+    }
+}
+
+impl Display for CpuSet {
+    /// Consecutive cores are written as range, e.g. "0-3,6".
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut ranges: Vec<String> = Vec::new();
+        let mut i = 0;
+        while i < self.cpus.len() {
+            let start = self.cpus[i];
+            while i + 1 < self.cpus.len() && self.cpus[i + 1] == self.cpus[i] + 1 {
+                i += 1;
+            }
+            ranges.push(match self.cpus[i] {
+                end if end == start => start.to_string(),
+                end => format!("{}-{}", start, end),
+            });
+            i += 1;
+        }
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parse {
+        use super::*;
+
+        #[test]
+        fn should_parse_ranges_and_single_cpus() {
+            let cpuset: CpuSet = "4-6,1,5".parse().unwrap();
+            assert_eq!(cpuset.cpus, vec![1, 4, 5, 6]);
+            assert_eq!(cpuset.to_string(), "1,4-6");
+        }
+
+        #[test]
+        fn should_reject_invalid_cpuset() {
+            assert!("a".parse::<CpuSet>().is_err());
+            assert!("3-1".parse::<CpuSet>().is_err());
+            assert!("".parse::<CpuSet>().is_err());
+        }
+
+        #[test]
+        fn should_reject_cpus_that_cannot_be_pinned() {
+            assert!(format!("0-{}", MAX_CPUS - 1).parse::<CpuSet>().is_ok());
+            assert!(format!("0,{}", MAX_CPUS).parse::<CpuSet>().is_err());
+        }
+
+        #[test]
+        fn should_write_label_without_commas() {
+            let cpuset: CpuSet = "0-1,4".parse().unwrap();
+            assert_eq!(cpuset.label(), "0-1+4");
+        }
+    }
+
+    #[test]
+    fn should_detect_overlap() {
+        let a: CpuSet = "0-1".parse().unwrap();
+        assert!(a.is_disjoint(&"2-3".parse().unwrap()));
+        assert!(!a.is_disjoint(&"1-2".parse().unwrap()));
+    }
+}
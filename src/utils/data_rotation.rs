@@ -0,0 +1,88 @@
+use std::ops::RangeInclusive;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Rotation of the data served by the web data source.
+/// Every `period`, the data source permutes the names and shells between the elements
+/// (ordered by atomic number) under `seed`, so a response only matches the data of the time
+/// it was fetched. Frameworks that cache the upstream responses fail the validation.
+///
+/// The version is derived from the wall clock, which the data source container shares with the host.
+#[derive(Clone, Copy, Debug)]
+pub struct DataRotation {
+    pub seed: u64,
+    pub period: Duration,
+}
+
+impl DataRotation {
+    pub fn version_at(&self, time: SystemTime) -> u64 {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap();
+        since_epoch.as_millis() as u64 / self.period.as_millis() as u64
+    }
+
+    /// Versions the data source may have served between sending the request and receiving the response.
+    pub fn versions(&self, sent_at: SystemTime, received_at: SystemTime) -> RangeInclusive<u64> {
+        self.version_at(sent_at)..=self.version_at(received_at)
+    }
+
+    /// Index of the element whose data is served for the element at `index` in the given version.
+    pub fn source_index(&self, version: u64, index: usize, len: usize) -> usize {
+        permutation(self.seed, version, len)[index]
+    }
+
+    /// Path and query of the control endpoint of the data source that enables this rotation.
+    pub fn control_path(&self) -> String {
+        format!(
+            "/rotation?seed={}&period_ms={}",
+            self.seed,
+            self.period.as_millis()
+        )
+    }
+}
+
+/// Must be the same as `permutation` in `src/benchmark/web/data/src/main.rs`.
+fn permutation(seed: u64, version: u64, len: usize) -> Vec<usize> {
+    let mut state = seed ^ version.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let mut next = || {
+        // splitmix64
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    let mut permutation: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        permutation.swap(i, j);
+    }
+    permutation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_match_permutation_of_data_source() {
+        // Computed with the data source, a change here breaks the rotation.
+        assert_eq!(permutation(42, 7, 8), vec![2, 3, 0, 4, 7, 6, 5, 1]);
+    }
+
+    #[test]
+    fn should_permute_every_index_once() {
+        let mut permutation = permutation(1, 1_760_000_000, 118);
+        permutation.sort();
+        assert_eq!(permutation, (0..118).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn should_include_all_versions_between_send_and_receive() {
+        let rotation = DataRotation {
+            seed: 0,
+            period: Duration::from_secs(1),
+        };
+        let sent_at = UNIX_EPOCH + Duration::from_millis(10_900);
+        let received_at = UNIX_EPOCH + Duration::from_millis(12_100);
+        assert_eq!(rotation.versions(sent_at, received_at), 10..=12);
+    }
+}
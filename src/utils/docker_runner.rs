@@ -50,7 +50,7 @@ fn run_shell(cmd: &[&str], working_dir: &str) {
     command.current_dir(Path::new(working_dir));
     let status = command
         .status()
-        .expect(&format!("failed to execute command: {:?}", cmd));
+        .unwrap_or_else(|_| panic!("failed to execute command: {:?}", cmd));
     if !status.success() {
         panic!("Command failed: {:?}", cmd);
     }
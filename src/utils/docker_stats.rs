@@ -29,7 +29,7 @@ impl DockerStatsReader {
         let ram_usage = Arc::clone(&self.ram_usage);
 
         let child = Command::new("docker")
-            .args(&["stats", "--format", "json"])
+            .args(["stats", "--format", "json"])
             .stdout(std::process::Stdio::piped())
             .spawn()
             .expect("failed to execute process");
@@ -91,7 +91,7 @@ impl DockerStatsReader {
     pub fn get_memory_usage(&self) -> MemoryUsage {
         let mut ram_usage = self.ram_usage.lock().unwrap();
         ram_usage.sort();
-        if ram_usage.len() == 0 {
+        if ram_usage.is_empty() {
             return MemoryUsage { median: 0, p99: 0 };
         }
         MemoryUsage {
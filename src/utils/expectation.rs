@@ -0,0 +1,456 @@
+use crate::utils::data_rotation::DataRotation;
+use hyper::header::HeaderMap;
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Expected JSON response, loaded from the `expected_response` of replay traces.
+/// It also deserializes from YAML, but no YAML file is read yet.
+///
+/// Plain values are compared as follows:
+/// - objects only check the listed keys, other keys are allowed
+/// - arrays must have the same length and match in order
+/// - numbers are compared by value, so `1` matches `1.0`
+/// - other values must be equal
+///
+/// An object with a single `$`-key is a matcher:
+/// - `{"$exact": ...}`: deep equality, no additional keys
+/// - `{"$approx": {"value": 1.0, "tolerance": 0.01}}`: number within the tolerance
+/// - `{"$regex": "^[A-Z][a-z]*$"}`: string matching the (unanchored) regex
+/// - `{"$unordered": [...]}`: array with the same length that matches in any order
+/// - `{"$exists": true}` / `{"$exists": false}`: required or forbidden key, any value
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Expectation {
+    Matcher(Matcher),
+    Object(IndexMap<String, Expectation>),
+    Array(Vec<Expectation>),
+    Literal(Value),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Matcher {
+    #[serde(rename = "$exact")]
+    Exact(Value),
+
+    #[serde(rename = "$approx")]
+    Approx { value: f64, tolerance: f64 },
+
+    #[serde(rename = "$regex", with = "regex_serde")]
+    Regex(Regex),
+
+    #[serde(rename = "$unordered")]
+    Unordered(Vec<Expectation>),
+
+    #[serde(rename = "$exists")]
+    Exists(bool),
+}
+
+/// Plain JSON is an expectation without matchers.
+impl From<Value> for Expectation {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Object(map) => Expectation::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, Expectation::from(value)))
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                Expectation::Array(items.into_iter().map(Expectation::from).collect())
+            }
+            value => Expectation::Literal(value),
+        }
+    }
+}
+
+impl Display for Expectation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl Expectation {
+    /// Checks the response. The error contains the JSON pointer of the first mismatch.
+    pub fn check(&self, actual: &Value) -> Result<(), String> {
+        self.check_at(actual, &mut String::new())
+    }
+
+    fn check_at(&self, actual: &Value, pointer: &mut String) -> Result<(), String> {
+        let mismatch = |pointer: &str| {
+            Err(format!(
+                "at \"{}\": expected {} but got {}",
+                pointer, self, actual
+            ))
+        };
+        match self {
+            Expectation::Literal(expected) => match (expected, actual) {
+                (Value::Number(expected), Value::Number(actual))
+                    if expected.as_f64() == actual.as_f64() =>
+                {
+                    Ok(())
+                }
+                (expected, actual) if expected == actual => Ok(()),
+                _ => mismatch(pointer),
+            },
+            Expectation::Object(expected) => {
+                let Some(actual) = actual.as_object() else {
+                    return mismatch(pointer);
+                };
+                for (key, expected_value) in expected {
+                    let len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                    match (expected_value, actual.get(key)) {
+                        (Expectation::Matcher(Matcher::Exists(false)), Some(_)) => {
+                            return Err(format!("at \"{}\": unexpected key", pointer));
+                        }
+                        (Expectation::Matcher(Matcher::Exists(false)), None) => {}
+                        (_, None) => {
+                            return Err(format!(
+                                "at \"{}\": expected {} but the key does not exist",
+                                pointer, expected_value
+                            ));
+                        }
+                        (_, Some(actual_value)) => {
+                            expected_value.check_at(actual_value, pointer)?
+                        }
+                    }
+                    pointer.truncate(len);
+                }
+                Ok(())
+            }
+            Expectation::Array(expected) => {
+                let Some(actual) = actual.as_array() else {
+                    return mismatch(pointer);
+                };
+                if actual.len() != expected.len() {
+                    return mismatch(pointer);
+                }
+                for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                    let len = pointer.len();
+                    pointer.push_str(&format!("/{index}"));
+                    expected.check_at(actual, pointer)?;
+                    pointer.truncate(len);
+                }
+                Ok(())
+            }
+            Expectation::Matcher(matcher) => {
+                let matches = match matcher {
+                    Matcher::Exact(expected) => expected == actual,
+                    Matcher::Approx { value, tolerance } => actual
+                        .as_f64()
+                        .is_some_and(|actual| (actual - value).abs() <= *tolerance),
+                    Matcher::Regex(regex) => actual.as_str().is_some_and(|s| regex.is_match(s)),
+                    Matcher::Unordered(expected) => match actual.as_array() {
+                        Some(actual) if actual.len() == expected.len() => {
+                            matches_unordered(expected, actual)
+                        }
+                        _ => false,
+                    },
+                    Matcher::Exists(exists) => *exists,
+                };
+                match matches {
+                    true => Ok(()),
+                    false => mismatch(pointer),
+                }
+            }
+        }
+    }
+}
+
+/// Expected response that depends on the data version of the web data source, see `DataRotation`.
+#[derive(Clone, Debug)]
+pub struct RotatedExpectation {
+    pub rotation: DataRotation,
+
+    /// Index of the requested element.
+    pub index: usize,
+
+    /// Expectation for the data of each element, ordered like the rotation.
+    pub variants: Arc<Vec<Expectation>>,
+}
+
+impl RotatedExpectation {
+    /// Passes if the response matches any version served while the request was in flight.
+    pub fn check(
+        &self,
+        actual: &Value,
+        sent_at: SystemTime,
+        received_at: SystemTime,
+    ) -> Result<(), String> {
+        let mut error = String::new();
+        for version in self.rotation.versions(sent_at, received_at) {
+            let source = self
+                .rotation
+                .source_index(version, self.index, self.variants.len());
+            match self.variants[source].check(actual) {
+                Ok(()) => return Ok(()),
+                Err(e) => error = e,
+            }
+        }
+        Err(format!(
+            "Stale data (version {}): {}",
+            self.rotation.version_at(received_at),
+            error
+        ))
+    }
+}
+
+/// Expected response headers. Names are case-insensitive.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HeaderExpectation {
+    /// Headers that must be present. A value is compared case-insensitively without parameters,
+    /// so `application/json` matches `application/json; charset=utf-8`. `None` matches any value.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub required: IndexMap<String, Option<String>>,
+
+    /// Headers that must not be present.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden: Vec<String>,
+}
+
+impl HeaderExpectation {
+    pub fn is_empty(&self) -> bool {
+        self.required.is_empty() && self.forbidden.is_empty()
+    }
+
+    pub fn check(&self, headers: &HeaderMap) -> Result<(), String> {
+        for (name, expected) in &self.required {
+            let Some(actual) = headers.get(name.as_str()) else {
+                return Err(format!(
+                    "Expected header \"{}\" but it does not exist",
+                    name
+                ));
+            };
+            let Some(expected) = expected else {
+                continue;
+            };
+            let actual = String::from_utf8_lossy(actual.as_bytes());
+            let without_parameters = actual.split(';').next().unwrap().trim();
+            if !without_parameters.eq_ignore_ascii_case(expected) {
+                return Err(format!(
+                    "Expected header \"{}: {}\" but got \"{}\"",
+                    name, expected, actual
+                ));
+            }
+        }
+        for name in &self.forbidden {
+            if let Some(actual) = headers.get(name.as_str()) {
+                return Err(format!(
+                    "Unexpected header \"{}: {}\"",
+                    name,
+                    String::from_utf8_lossy(actual.as_bytes())
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every expected item must match a different actual item.
+/// The first matching item is taken, so more specific expectations should come first.
+fn matches_unordered(expected: &[Expectation], actual: &[Value]) -> bool {
+    let mut used = vec![false; actual.len()];
+    expected.iter().all(|expected| {
+        let found = actual
+            .iter()
+            .enumerate()
+            .find(|(index, actual)| !used[*index] && expected.check(actual).is_ok());
+        match found {
+            Some((index, _)) => {
+                used[index] = true;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+mod regex_serde {
+    use regex::Regex;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(regex.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn expectation(json: &str) -> Expectation {
+        serde_json::from_str(json).unwrap()
+    }
+
+    mod plain {
+        use super::*;
+
+        #[test]
+        fn should_match_subset_of_object() {
+            let expected = Expectation::from(json!({"name": "Helium", "shells": [2]}));
+            let actual = json!({"name": "Helium", "number": 2, "shells": [2]});
+            assert!(expected.check(&actual).is_ok());
+        }
+
+        #[test]
+        fn should_compare_numbers_by_value() {
+            assert!(Expectation::from(json!(1)).check(&json!(1.0)).is_ok());
+            assert!(Expectation::from(json!(1)).check(&json!("1")).is_err());
+        }
+
+        #[test]
+        fn should_report_pointer_of_first_mismatch() {
+            let expected = Expectation::from(json!({"data": {"a/b": [1, 2]}}));
+            let actual = json!({"data": {"a/b": [1, 3]}});
+            assert_eq!(
+                expected.check(&actual).unwrap_err(),
+                "at \"/data/a~1b/1\": expected 2 but got 3"
+            );
+        }
+
+        #[test]
+        fn should_report_missing_key() {
+            let expected = Expectation::from(json!({"name": "Helium"}));
+            assert_eq!(
+                expected.check(&json!({})).unwrap_err(),
+                "at \"/name\": expected \"Helium\" but the key does not exist"
+            );
+        }
+    }
+
+    mod headers {
+        use super::*;
+        use hyper::header::HeaderValue;
+
+        fn expectation() -> HeaderExpectation {
+            HeaderExpectation {
+                required: IndexMap::from([
+                    (
+                        "Content-Type".to_string(),
+                        Some("application/json".to_string()),
+                    ),
+                    ("content-length".to_string(), None),
+                ]),
+                forbidden: vec!["expires".to_string()],
+            }
+        }
+
+        fn headers(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+            headers
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+                .collect()
+        }
+
+        #[test]
+        fn should_ignore_case_and_parameters() {
+            let actual = headers(&[
+                ("content-type", "Application/JSON; charset=utf-8"),
+                ("content-length", "2"),
+            ]);
+            assert!(expectation().check(&actual).is_ok());
+        }
+
+        #[test]
+        fn should_report_wrong_content_type() {
+            let actual = headers(&[("content-type", "text/plain"), ("content-length", "2")]);
+            assert_eq!(
+                expectation().check(&actual).unwrap_err(),
+                "Expected header \"Content-Type: application/json\" but got \"text/plain\""
+            );
+        }
+
+        #[test]
+        fn should_report_missing_and_forbidden_headers() {
+            let chunked = headers(&[
+                ("content-type", "application/json"),
+                ("transfer-encoding", "chunked"),
+            ]);
+            assert_eq!(
+                expectation().check(&chunked).unwrap_err(),
+                "Expected header \"content-length\" but it does not exist"
+            );
+
+            let cached = headers(&[
+                ("content-type", "application/json"),
+                ("content-length", "2"),
+                ("expires", "0"),
+            ]);
+            assert_eq!(
+                expectation().check(&cached).unwrap_err(),
+                "Unexpected header \"expires: 0\""
+            );
+        }
+    }
+
+    mod matchers {
+        use super::*;
+
+        #[test]
+        fn should_reject_additional_keys_with_exact() {
+            let expected = expectation(r#"{"$exact": {"a": 1}}"#);
+            assert!(expected.check(&json!({"a": 1})).is_ok());
+            assert!(expected.check(&json!({"a": 1, "b": 2})).is_err());
+        }
+
+        #[test]
+        fn should_match_float_with_tolerance() {
+            let expected =
+                expectation(r#"{"mass": {"$approx": {"value": 4.0026, "tolerance": 0.001}}}"#);
+            assert!(expected.check(&json!({"mass": 4.0025})).is_ok());
+            assert!(expected.check(&json!({"mass": 4.1})).is_err());
+        }
+
+        #[test]
+        fn should_match_regex() {
+            let expected = expectation(r#"{"symbol": {"$regex": "^[A-Z][a-z]?$"}}"#);
+            assert!(expected.check(&json!({"symbol": "He"})).is_ok());
+            assert!(expected.check(&json!({"symbol": "helium"})).is_err());
+        }
+
+        #[test]
+        fn should_match_unordered_array() {
+            let expected = expectation(
+                r#"{"$unordered": [1, {"$approx": {"value": 2.0, "tolerance": 0.5}}, 3]}"#,
+            );
+            assert!(expected.check(&json!([3, 2.2, 1])).is_ok());
+            assert!(expected.check(&json!([3, 3, 1])).is_err());
+        }
+
+        #[test]
+        fn should_check_required_and_forbidden_keys() {
+            let expected =
+                expectation(r#"{"id": {"$exists": true}, "password": {"$exists": false}}"#);
+            assert!(expected.check(&json!({"id": 7})).is_ok());
+            assert!(expected.check(&json!({})).is_err());
+            assert_eq!(
+                expected
+                    .check(&json!({"id": 7, "password": "x"}))
+                    .unwrap_err(),
+                "at \"/password\": unexpected key"
+            );
+        }
+
+        #[test]
+        fn should_parse_yaml() {
+            let expected: Expectation = serde_yaml::from_str(
+                "name: Helium\nshells:\n  $unordered: [2]\nsymbol:\n  $regex: ^He$\n",
+            )
+            .unwrap();
+            assert!(expected
+                .check(&json!({"name": "Helium", "shells": [2], "symbol": "He"}))
+                .is_ok());
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use crate::utils::http_load_tester::{
+    request_order, validate_sample, ConnectionMode, ErrorCounts, HttpLoadConfig, HttpLoadResult,
+    LoadMode, MeasurementWindow, PendingValidationResponse, PreparedHttpRequest, Protocol,
+};
+use crate::utils::load_engine::Engine;
+use crate::utils::percentile::Histogram;
+use hyper::Uri;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// Number of requests of the request order handed to the tool, which cycles through them.
+const REQUEST_ORDER_LENGTH: usize = 10_000;
+
+/// Marker of the line printed by the wrk script.
+const WRK_RESULT_MARKER: &str = "SHARKBENCH_RESULT";
+
+/// Error message of oha for requests that were still in flight when the duration was over.
+const OHA_DEADLINE_ERROR: &str = "aborted due to deadline";
+
+/// What the tools report, normalized to the same units.
+#[derive(Debug)]
+struct ToolSummary {
+    /// Number of requests sent, including the failed ones.
+    sent_count: i32,
+    errors: ErrorCounts,
+    latency_median: Duration,
+    latency_p90: Duration,
+    latency_p99: Duration,
+    latency_p999: Duration,
+    latency_max: Duration,
+
+    /// Only reported by k6.
+    ttfb: Option<[Duration; 4]>,
+}
+
+/// Runs the load test with a locally installed wrk, oha or k6 instead of the built-in client.
+/// The responses are validated by sending every request once before the load test.
+/// The tools run for the whole duration without ramp-up and excluded periods,
+/// and they do not report the results per route or per second.
+pub fn run_external_load_test(
+    config: &HttpLoadConfig,
+    requests: &[PreparedHttpRequest],
+    request_validator: fn(&PendingValidationResponse) -> Result<(), String>,
+) -> HttpLoadResult {
+    let engine = config.engine;
+    if !engine.supports(config.protocol) {
+        panic!("The {} engine does not support {}", engine, config.protocol);
+    }
+    if config.schedule.is_some() {
+        panic!("The {} engine does not support replays", engine);
+    }
+    if config.trace_path.is_some() {
+        panic!("The {} engine does not support recording", engine);
+    }
+    if !(config.ramp_up + config.exclude_start + config.exclude_end).is_zero() {
+        println!(
+            " -> {} does not support ramp-up and excluded periods, measuring the whole run",
+            engine
+        );
+    }
+
+    let sample = validate_sample(config, requests, request_validator);
+
+    let urls: Vec<&str> = request_order(
+        requests,
+        config.route_weights.as_ref(),
+        config.seed,
+        REQUEST_ORDER_LENGTH,
+    )
+    .into_iter()
+    .map(|index| requests[index].url.as_str())
+    .collect();
+
+    let (summary, connections) = match engine {
+        Engine::Wrk => (
+            parse_wrk_output(&run_wrk(config, &urls)),
+            config.connections,
+        ),
+        Engine::Oha => (
+            parse_oha_output(&run_oha(config, &urls)),
+            match config.protocol {
+                Protocol::Http1 => config.max_in_flight,
+                Protocol::H2c => config.connections.max(1),
+            },
+        ),
+        Engine::K6 => (
+            parse_k6_output(&run_k6(config, &urls)),
+            config.max_in_flight,
+        ),
+        Engine::Reqwest | Engine::Hyper => panic!("{} is not an external engine", engine),
+    };
+
+    let mut errors = summary.errors;
+    errors.invalid += sample.errors.invalid;
+    let success_count = summary.sent_count - summary.errors.total();
+    if success_count <= 0 {
+        panic!("No successful requests. Something is wrong. Run with --verbose to see the output of {}.", engine);
+    }
+    if summary.errors.total() as f64 > config.error_budget * summary.sent_count as f64 {
+        panic!(
+            "{} of {} requests failed ({}), error budget is {} ({})",
+            summary.errors.total(),
+            summary.sent_count,
+            summary.errors,
+            config.error_budget,
+            config.seed_info()
+        );
+    }
+
+    let seconds = config.duration.as_secs_f64();
+    let rps = (success_count as f64 / seconds) as i32;
+    let [ttfb_median, ttfb_p90, ttfb_p99, ttfb_p999] = summary.ttfb.unwrap_or_default();
+    HttpLoadResult {
+        success_count,
+        fail_count: errors.total(),
+        errors,
+        total_time: config.duration,
+        rps_median: rps,
+        rps_p99: 0,
+        latency_median: summary.latency_median,
+        latency_p90: summary.latency_p90,
+        latency_p99: summary.latency_p99,
+        latency_p999: summary.latency_p999,
+        latency_max: summary.latency_max,
+        ttfb_median,
+        ttfb_p90,
+        ttfb_p99,
+        ttfb_p999,
+        bytes_per_response: sample.bytes_per_response,
+        latency_histogram: Histogram::new(),
+        target_rps: match config.load_mode {
+            LoadMode::Closed => None,
+            LoadMode::Open => config.target_rps.map(|rps| rps as i32),
+        },
+        achieved_rps: (summary.sent_count as f64 / seconds) as i32,
+        rps_per_second: vec![],
+        measurement_window: MeasurementWindow {
+            start: Duration::ZERO,
+            end: config.duration,
+        },
+        connections,
+        max_in_flight: config.max_in_flight,
+        peak_in_flight: 0,
+        routes: IndexMap::new(),
+    }
+}
+
+fn run_wrk(config: &HttpLoadConfig, urls: &[&str]) -> String {
+    if config.load_mode == LoadMode::Open {
+        panic!("The wrk engine does not support the open-loop load mode");
+    }
+
+    let base: Uri = urls[0].parse().expect("Invalid URL");
+    let paths: Vec<String> = urls
+        .iter()
+        .map(|url| {
+            let uri: Uri = url.parse().expect("Invalid URL");
+            lua_string(uri.path_and_query().unwrap().as_str())
+        })
+        .collect();
+    let script = format!(
+        r#"local paths = {{{paths}}}
+local i = 0
+
+request = function()
+  i = i + 1
+  return wrk.format("GET", paths[(i - 1) % #paths + 1])
+end
+
+done = function(summary, latency, requests)
+  local errors = summary.errors
+  io.write(string.format("{marker} %d %d %d %d %d %d %d %d %d %d %d\n",
+    summary.requests, errors.connect, errors.read, errors.write, errors.status, errors.timeout,
+    latency:percentile(50), latency:percentile(90), latency:percentile(99), latency:percentile(99.9),
+    latency.max))
+end
+"#,
+        paths = paths.join(", "),
+        marker = WRK_RESULT_MARKER,
+    );
+    let script_file = TempFile::new("wrk.lua", &script);
+
+    let mut args = vec![
+        format!(
+            "--threads={}",
+            config.worker_threads.min(config.connections).max(1)
+        ),
+        format!("--connections={}", config.connections),
+        format!("--duration={}s", config.duration.as_secs()),
+        "--timeout=15s".to_string(),
+        format!("--script={}", script_file.path.display()),
+    ];
+    if config.connection_mode == ConnectionMode::Close {
+        args.push("--header=Connection: close".to_string());
+    }
+    args.push(format!(
+        "{}://{}",
+        base.scheme_str().unwrap(),
+        base.authority().unwrap()
+    ));
+    run_tool("wrk", &args, config)
+}
+
+fn run_oha(config: &HttpLoadConfig, urls: &[&str]) -> String {
+    let urls_file = TempFile::new("oha-urls.txt", &urls.join("\n"));
+
+    let mut args = vec![
+        "--no-tui".to_string(),
+        "--json".to_string(),
+        "-z".to_string(),
+        format!("{}s", config.duration.as_secs()),
+    ];
+    match config.protocol {
+        Protocol::Http1 => args.extend(["-c".to_string(), config.max_in_flight.to_string()]),
+        Protocol::H2c => {
+            let connections = config.connections.max(1);
+            args.push("--http2".to_string());
+            args.extend(["-c".to_string(), connections.to_string()]);
+            args.extend([
+                "-p".to_string(),
+                config.max_in_flight.div_ceil(connections).to_string(),
+            ]);
+        }
+    }
+    if config.connection_mode == ConnectionMode::Close {
+        args.push("--disable-keepalive".to_string());
+    }
+    if config.load_mode == LoadMode::Open {
+        let target_rps = config
+            .target_rps
+            .expect("target_rps is required in open-loop mode");
+        args.extend(["-q".to_string(), target_rps.to_string()]);
+        args.push("--latency-correction".to_string());
+    }
+    args.push("--urls-from-file".to_string());
+    args.push(urls_file.path.display().to_string());
+    run_tool("oha", &args, config)
+}
+
+fn run_k6(config: &HttpLoadConfig, urls: &[&str]) -> String {
+    let duration = format!("{}s", config.duration.as_secs());
+    let scenario = match config.load_mode {
+        LoadMode::Closed => format!(
+            "{{ executor: 'constant-vus', vus: {}, duration: '{}' }}",
+            config.max_in_flight, duration
+        ),
+        LoadMode::Open => format!(
+            "{{ executor: 'constant-arrival-rate', rate: {}, timeUnit: '1s', duration: '{}', preAllocatedVUs: {} }}",
+            config.target_rps.expect("target_rps is required in open-loop mode"),
+            duration,
+            config.max_in_flight
+        ),
+    };
+    let urls: Vec<String> = urls.iter().map(|url| format!("'{}'", url)).collect();
+    let script = format!(
+        r#"import http from 'k6/http';
+import exec from 'k6/execution';
+
+const urls = [{urls}];
+
+export const options = {{
+  scenarios: {{ load: {scenario} }},
+  summaryTrendStats: ['med', 'p(90)', 'p(99)', 'p(99.9)', 'max'],
+  noConnectionReuse: {close},
+  discardResponseBodies: true,
+}};
+
+export default function () {{
+  http.get(urls[exec.scenario.iterationInTest % urls.length], {{ timeout: '15s' }});
+}}
+"#,
+        urls = urls.join(", "),
+        close = config.connection_mode == ConnectionMode::Close,
+    );
+    let script_file = TempFile::new("k6.js", &script);
+    let summary_file = TempFile::new("k6-summary.json", "");
+
+    run_tool(
+        "k6",
+        &[
+            "run".to_string(),
+            "--quiet".to_string(),
+            format!("--summary-export={}", summary_file.path.display()),
+            script_file.path.display().to_string(),
+        ],
+        config,
+    );
+    fs::read_to_string(&summary_file.path).expect("Failed to read k6 summary")
+}
+
+/// Runs the tool (pinned to the cpuset of the load generator) and returns its standard output.
+fn run_tool(program: &str, args: &[String], config: &HttpLoadConfig) -> String {
+    let mut command = match &config.cpuset {
+        Some(cpuset) => {
+            let mut command = Command::new("taskset");
+            command
+                .arg("--cpu-list")
+                .arg(cpuset.to_string())
+                .arg(program);
+            command
+        }
+        None => Command::new(program),
+    };
+    command.args(args);
+    if config.verbose {
+        println!(" -> Running {:?}", command);
+    }
+
+    let output = command.output().unwrap_or_else(|e| match e.kind() {
+        ErrorKind::NotFound => panic!("{} is not installed", program),
+        _ => panic!("Failed to run {}: {}", program, e),
+    });
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if config.verbose {
+        println!("{}", stdout);
+    }
+    if !output.status.success() {
+        panic!(
+            "{} failed ({}):\n{}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    stdout
+}
+
+fn parse_wrk_output(output: &str) -> ToolSummary {
+    let line = output
+        .lines()
+        .find_map(|line| line.strip_prefix(WRK_RESULT_MARKER))
+        .unwrap_or_else(|| panic!("No result in wrk output:\n{}", output));
+    let values: Vec<u64> = line
+        .split_whitespace()
+        .map(|value| value.parse().expect("Invalid number in wrk output"))
+        .collect();
+    let [requests, connect, read, write, status, timeout, p50, p90, p99, p999, max] =
+        values[..].try_into().expect("Unexpected wrk output");
+
+    // wrk only counts the requests that received a response, so the failed ones are added.
+    let errors = ErrorCounts {
+        timeout: timeout as i32,
+        connection_refused: connect as i32,
+        connection_reset: (read + write) as i32,
+        status: status as i32,
+        invalid: 0,
+        other: 0,
+    };
+    ToolSummary {
+        sent_count: (requests + connect + read + write + timeout) as i32,
+        errors,
+        latency_median: Duration::from_micros(p50),
+        latency_p90: Duration::from_micros(p90),
+        latency_p99: Duration::from_micros(p99),
+        latency_p999: Duration::from_micros(p999),
+        latency_max: Duration::from_micros(max),
+        ttfb: None,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OhaOutput {
+    summary: OhaSummary,
+    latency_percentiles: HashMap<String, Option<f64>>,
+    status_code_distribution: HashMap<String, i32>,
+    error_distribution: HashMap<String, i32>,
+}
+
+#[derive(Deserialize)]
+struct OhaSummary {
+    slowest: Option<f64>,
+}
+
+fn parse_oha_output(output: &str) -> ToolSummary {
+    let output: OhaOutput = serde_json::from_str(output)
+        .unwrap_or_else(|e| panic!("Invalid oha output: {}\n{}", e, output));
+
+    let mut errors = ErrorCounts::default();
+    let mut sent_count = 0;
+    for (status, count) in &output.status_code_distribution {
+        sent_count += count;
+        if status != "200" {
+            errors.status += count;
+        }
+    }
+    for (message, count) in &output.error_distribution {
+        if message == OHA_DEADLINE_ERROR {
+            continue;
+        }
+        sent_count += count;
+        let message = message.to_lowercase();
+        if message.contains("timeout") || message.contains("timed out") {
+            errors.timeout += count;
+        } else if message.contains("refused") {
+            errors.connection_refused += count;
+        } else if message.contains("reset") || message.contains("broken pipe") {
+            errors.connection_reset += count;
+        } else {
+            errors.other += count;
+        }
+    }
+
+    // Durations are reported in seconds.
+    let seconds = |value: Option<f64>| Duration::from_secs_f64(value.unwrap_or(0.0));
+    let percentile = |key: &str| seconds(output.latency_percentiles.get(key).copied().flatten());
+    ToolSummary {
+        sent_count,
+        errors,
+        latency_median: percentile("p50"),
+        latency_p90: percentile("p90"),
+        latency_p99: percentile("p99"),
+        latency_p999: percentile("p99.9"),
+        latency_max: seconds(output.summary.slowest),
+        ttfb: None,
+    }
+}
+
+#[derive(Deserialize)]
+struct K6Output {
+    metrics: HashMap<String, HashMap<String, f64>>,
+}
+
+fn parse_k6_output(output: &str) -> ToolSummary {
+    let output: K6Output = serde_json::from_str(output)
+        .unwrap_or_else(|e| panic!("Invalid k6 summary: {}\n{}", e, output));
+    let metric = |metric: &str, key: &str| {
+        *output
+            .metrics
+            .get(metric)
+            .and_then(|values| values.get(key))
+            .unwrap_or_else(|| panic!("Missing {} of {} in k6 summary", key, metric))
+    };
+
+    // Trends are reported in milliseconds.
+    let trend = |metric_name: &str| {
+        ["med", "p(90)", "p(99)", "p(99.9)", "max"]
+            .map(|key| Duration::from_secs_f64(metric(metric_name, key) / 1000.0))
+    };
+    let [latency_median, latency_p90, latency_p99, latency_p999, latency_max] =
+        trend("http_req_duration");
+    let [ttfb_median, ttfb_p90, ttfb_p99, ttfb_p999, _] = trend("http_req_waiting");
+
+    // k6 only reports whether a request has failed, not why. "passes" counts the failed requests.
+    let errors = ErrorCounts {
+        other: metric("http_req_failed", "passes") as i32,
+        ..ErrorCounts::default()
+    };
+    ToolSummary {
+        sent_count: metric("http_reqs", "count") as i32,
+        errors,
+        latency_median,
+        latency_p90,
+        latency_p99,
+        latency_p999,
+        latency_max,
+        ttfb: Some([ttfb_median, ttfb_p90, ttfb_p99, ttfb_p999]),
+    }
+}
+
+fn lua_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A file in the temp directory that is deleted when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new(name: &str, content: &str) -> TempFile {
+        let path = std::env::temp_dir().join(format!("sharkbench-{}-{}", std::process::id(), name));
+        fs::write(&path, content).expect("Failed to write temp file");
+        TempFile { path }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_wrk_output() {
+        let output = "Running 15s test @ http://localhost:3000\n  2 threads and 32 connections\n\
+            SHARKBENCH_RESULT 15000 2 3 0 5 1 1200 2500 4800 9100 15000\n";
+        let summary = parse_wrk_output(output);
+        assert_eq!(summary.sent_count, 15006);
+        assert_eq!(summary.errors.total(), 11);
+        assert_eq!(summary.errors.connection_refused, 2);
+        assert_eq!(summary.errors.status, 5);
+        assert_eq!(summary.latency_median, Duration::from_micros(1200));
+        assert_eq!(summary.latency_max, Duration::from_micros(15000));
+    }
+
+    #[test]
+    fn should_parse_oha_output() {
+        let output = r#"{
+            "summary": {"successRate": 0.99, "total": 15.0, "slowest": 0.02, "fastest": 0.0001, "average": 0.001},
+            "latencyPercentiles": {"p50": 0.001, "p90": 0.002, "p99": 0.004, "p99.9": 0.008},
+            "statusCodeDistribution": {"200": 990, "500": 4},
+            "errorDistribution": {"connection refused": 6, "aborted due to deadline": 32}
+        }"#;
+        let summary = parse_oha_output(output);
+        assert_eq!(summary.sent_count, 1000);
+        assert_eq!(summary.errors.status, 4);
+        assert_eq!(summary.errors.connection_refused, 6);
+        assert_eq!(summary.latency_p999, Duration::from_millis(8));
+        assert_eq!(summary.latency_max, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn should_parse_k6_summary() {
+        let output = r#"{"metrics": {
+            "http_reqs": {"count": 1000, "rate": 66.6},
+            "http_req_failed": {"passes": 3, "fails": 997, "value": 0.003},
+            "http_req_duration": {"med": 1.5, "p(90)": 2, "p(99)": 4, "p(99.9)": 8, "max": 20},
+            "http_req_waiting": {"med": 1, "p(90)": 1.5, "p(99)": 3, "p(99.9)": 6, "max": 18}
+        }}"#;
+        let summary = parse_k6_output(output);
+        assert_eq!(summary.sent_count, 1000);
+        assert_eq!(summary.errors.other, 3);
+        assert_eq!(summary.latency_median, Duration::from_micros(1500));
+        assert_eq!(summary.ttfb.unwrap()[2], Duration::from_millis(3));
+    }
+}
@@ -1,165 +1,1053 @@
+use crate::utils::cpu_affinity::CpuSet;
+use crate::utils::expectation::{Expectation, HeaderExpectation, RotatedExpectation};
+use crate::utils::external_load_tester::run_external_load_test;
+use crate::utils::load_engine::{Engine, HyperEngine, LoadEngine, RequestError, ReqwestEngine};
 use crate::utils::percentile;
-use crate::utils::serialization::SerializedValue;
+use crate::utils::percentile::Histogram;
+use crate::utils::request_trace::{Outcome, RecordedResponse, TraceRecorder, RECORDED_BODY_RATE};
+use clap::ValueEnum;
+use indexmap::IndexMap;
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use reqwest;
-use reqwest::StatusCode;
-use std::collections::HashMap;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::error::Error;
-use std::fmt::Write;
-use std::time::Duration;
+use std::fmt::{Display, Formatter, Write};
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tokio;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tokio::task::JoinHandle;
 use tokio::{task, time};
 
 pub struct HttpLoadResult {
     pub success_count: i32,
     pub fail_count: i32,
+    pub errors: ErrorCounts,
     pub total_time: Duration,
     pub rps_median: i32,
+
+    /// Worst second of the measurement window, 0 for external engines, which do not report it.
     pub rps_p99: i32,
     pub latency_median: Duration,
+    pub latency_p90: Duration,
     pub latency_p99: Duration,
+    pub latency_p999: Duration,
+    pub latency_max: Duration,
+
+    /// Time until the response headers have arrived, without the body transfer.
+    pub ttfb_median: Duration,
+    pub ttfb_p90: Duration,
+    pub ttfb_p99: Duration,
+    pub ttfb_p999: Duration,
+
+    /// Average body size of the successful responses in bytes.
+    pub bytes_per_response: i32,
+
+    /// Latency of all successful requests in microseconds.
+    pub latency_histogram: Histogram,
+
+    /// The requested rate in open-loop mode, `None` in closed-loop mode.
+    pub target_rps: Option<i32>,
+
+    /// Number of requests actually sent per second (successful or not).
+    pub achieved_rps: i32,
+
+    /// Successful requests per wall-clock second of the measurement window in chronological order.
+    /// Partial seconds are not included.
+    pub rps_per_second: Vec<i32>,
+
+    pub measurement_window: MeasurementWindow,
+
+    /// The enforced limits of the load test.
+    pub connections: usize,
+    pub max_in_flight: usize,
+
+    /// Highest number of requests that have been in flight at the same time.
+    /// 0 for external engines, which do not report it.
+    pub peak_in_flight: usize,
+
+    /// Results per route label, in the order of first appearance in the requests.
+    pub routes: IndexMap<String, RouteResult>,
 }
 
+pub struct RouteResult {
+    pub success_count: i32,
+    pub fail_count: i32,
+
+    /// Share of the successful requests in the measurement window in percent.
+    pub rps_share: i32,
+    pub latency_median: Duration,
+    pub latency_p90: Duration,
+    pub latency_p99: Duration,
+    pub latency_p999: Duration,
+    pub ttfb_median: Duration,
+    pub ttfb_p99: Duration,
+
+    /// Average body size of the successful responses in bytes.
+    pub bytes_per_response: i32,
+
+    /// Headers the server has sent, taken from the first response with the expected status.
+    pub headers: Vec<(String, String)>,
+}
+
+/// Counters of a single route within one task.
 #[derive(Clone)]
+struct RouteStats {
+    success_count: i32,
+    fail_count: i32,
+    latency_us: Histogram,
+    ttfb_us: Histogram,
+    body_bytes: u64,
+
+    /// Headers of the first response with the expected status.
+    headers: Option<Vec<(String, String)>>,
+}
+
+impl RouteStats {
+    fn new() -> RouteStats {
+        RouteStats {
+            success_count: 0,
+            fail_count: 0,
+            latency_us: Histogram::new(),
+            ttfb_us: Histogram::new(),
+            body_bytes: 0,
+            headers: None,
+        }
+    }
+
+    fn merge(&mut self, other: &RouteStats) {
+        self.success_count += other.success_count;
+        self.fail_count += other.fail_count;
+        self.latency_us.merge(&other.latency_us);
+        self.ttfb_us.merge(&other.ttfb_us);
+        self.body_bytes += other.body_bytes;
+        if self.headers.is_none() {
+            self.headers = other.headers.clone();
+        }
+    }
+}
+
+/// Failed requests by category. The sum is the `fail_count`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ErrorCounts {
+    pub timeout: i32,
+    pub connection_refused: i32,
+    pub connection_reset: i32,
+
+    /// Responses with a status code other than 200.
+    pub status: i32,
+
+    /// Responses that did not pass the validation.
+    pub invalid: i32,
+
+    /// Any other error, e.g. a malformed HTTP response.
+    pub other: i32,
+}
+
+impl ErrorCounts {
+    pub fn total(&self) -> i32 {
+        self.timeout
+            + self.connection_refused
+            + self.connection_reset
+            + self.status
+            + self.invalid
+            + self.other
+    }
+
+    fn merge(&mut self, other: &ErrorCounts) {
+        self.timeout += other.timeout;
+        self.connection_refused += other.connection_refused;
+        self.connection_reset += other.connection_reset;
+        self.status += other.status;
+        self.invalid += other.invalid;
+        self.other += other.other;
+    }
+
+    fn record_request_error(&mut self, err: &RequestError) {
+        if err.is_timeout() {
+            self.timeout += 1;
+            return;
+        }
+
+        let mut source = err.source();
+        while let Some(e) = source {
+            if let Some(io_error) = e.downcast_ref::<std::io::Error>() {
+                match io_error.kind() {
+                    ErrorKind::ConnectionRefused => {
+                        self.connection_refused += 1;
+                        return;
+                    }
+                    ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof => {
+                        self.connection_reset += 1;
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            source = e.source();
+        }
+
+        self.other += 1;
+    }
+}
+
+impl Display for ErrorCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "timeout: {}, connection refused: {}, connection reset: {}, status: {}, invalid: {}, other: {}",
+            self.timeout,
+            self.connection_refused,
+            self.connection_reset,
+            self.status,
+            self.invalid,
+            self.other
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadMode {
+    /// Each task sends the next request as soon as the previous one has finished.
+    Closed,
+
+    /// Requests are sent according to a fixed schedule derived from `target_rps`.
+    /// Latency is measured from the intended send time to correct for coordinated omission.
+    Open,
+}
+
+impl Display for LoadMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadMode::Closed => write!(f, "closed"),
+            LoadMode::Open => write!(f, "open"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// HTTP/1.1, every task has its own keep-alive connection.
+    Http1,
+
+    /// HTTP/2 over cleartext with prior knowledge. The tasks are multiplexed as streams
+    /// over `connections` connections.
+    H2c,
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::Http1 => write!(f, "http1"),
+            Protocol::H2c => write!(f, "h2c"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectionMode {
+    /// Connections are reused for subsequent requests.
+    KeepAlive,
+
+    /// Every request opens a new connection and sends `Connection: close`.
+    /// Measures the cost of accepting connections, like behind proxies without connection reuse.
+    Close,
+}
+
+impl Display for ConnectionMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionMode::KeepAlive => write!(f, "keep-alive"),
+            ConnectionMode::Close => write!(f, "close"),
+        }
+    }
+}
+
+pub struct HttpLoadConfig {
+    /// Maximum number of requests in flight. Each of these tasks sends one request at a time.
+    pub max_in_flight: usize,
+    pub protocol: Protocol,
+
+    /// Number of connections shared by the tasks.
+    /// A HTTP/1.1 connection carries one request at a time, a HTTP/2 connection multiple streams.
+    pub connections: usize,
+    pub connection_mode: ConnectionMode,
+    pub engine: Engine,
+
+    /// Number of worker threads of the load generator.
+    pub worker_threads: usize,
+
+    /// Cores the threads of the load generator are pinned to. If `None`, they may run on any core.
+    pub cpuset: Option<CpuSet>,
+
+    /// Length of the measurement window.
+    pub duration: Duration,
+
+    /// The tasks are started linearly over this period before the measurement window.
+    pub ramp_up: Duration,
+
+    /// Time after the ramp-up that is not measured.
+    pub exclude_start: Duration,
+
+    /// Time after the measurement window until the load test stops.
+    pub exclude_end: Duration,
+    pub load_mode: LoadMode,
+
+    /// Total requests per second across all tasks. Required in open-loop mode.
+    pub target_rps: Option<usize>,
+    pub validation: ValidationConfig,
+
+    /// Relative weight per route label. If `None`, all requests are sent equally often.
+    pub route_weights: Option<IndexMap<String, u32>>,
+
+    /// Send time of each request relative to the start, to replay a recorded trace.
+    /// If set, every request is sent once at its send time (or as soon as a task is free)
+    /// instead of following the load mode and the route weights.
+    pub schedule: Option<Arc<Vec<Duration>>>,
+
+    /// Every sent request is written to this JSONL file, see `TraceRecorder`.
+    pub trace_path: Option<String>,
+
+    /// Fraction of sent requests that may fail before the load test is considered failed.
+    pub error_budget: f64,
+
+    /// Seed of the request order. Each task derives its own seed from it,
+    /// so the same seed sends the same sequence of requests per task.
+    pub seed: u64,
+
+    /// Base seed (`--seed`) and iteration that `seed` is derived from.
+    /// Printed on failures, so the request order can be reproduced.
+    pub base_seed: u64,
+    pub iteration: u64,
+    pub verbose: bool,
+}
+
+impl HttpLoadConfig {
+    /// How to reproduce the request order, e.g. "seed = 1234, iteration = 2".
+    pub fn seed_info(&self) -> String {
+        format!("seed = {}, iteration = {}", self.base_seed, self.iteration)
+    }
+}
+
+impl HttpLoadConfig {
+    pub fn measurement_window(&self) -> MeasurementWindow {
+        let start = self.ramp_up + self.exclude_start;
+        MeasurementWindow {
+            start,
+            end: start + self.duration,
+        }
+    }
+
+    fn total_duration(&self) -> Duration {
+        self.measurement_window().end + self.exclude_end
+    }
+}
+
+/// Part of the load test that is used for the results, relative to its start.
+#[derive(Clone, Copy, Debug)]
+pub struct MeasurementWindow {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl MeasurementWindow {
+    fn contains(&self, elapsed: Duration) -> bool {
+        elapsed >= self.start && elapsed < self.end
+    }
+}
+
+impl Display for MeasurementWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}s-{}s",
+            self.start.as_secs_f64(),
+            self.end.as_secs_f64()
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    /// Fraction of successful responses that are validated (0.0 - 1.0).
+    pub sample_rate: f64,
+
+    /// Maximum number of invalid responses whose bodies are kept for the report.
+    pub max_kept_invalid: usize,
+
+    /// Abort the load test as soon as this many invalid responses have been received.
+    /// Defaults to `DEFAULT_FAIL_FAST_THRESHOLD` without an error budget, and to no threshold with one.
+    pub fail_fast_threshold: Option<usize>,
+}
+
+const DEFAULT_FAIL_FAST_THRESHOLD: usize = 10;
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            sample_rate: 1.0,
+            max_kept_invalid: 10,
+            fail_fast_threshold: None,
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// Resolves the default of `fail_fast_threshold`.
+    /// With an error budget, invalid responses count towards the budget, which is checked after the run.
+    fn with_error_budget(&self, error_budget: f64) -> ValidationConfig {
+        ValidationConfig {
+            fail_fast_threshold: self
+                .fail_fast_threshold
+                .or((error_budget == 0.0).then_some(DEFAULT_FAIL_FAST_THRESHOLD)),
+            ..self.clone()
+        }
+    }
+}
+
+/// A response that did not pass the validation, kept for the report.
+pub struct InvalidResponse {
+    pub url: String,
+    pub expected: String,
+    pub actual: String,
+    pub reason: String,
+}
+
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Maximum number of characters of a kept response body.
+const MAX_KEPT_BODY_LENGTH: usize = 1000;
+
+/// Successful requests counted per second of wall-clock time, shared between all tasks.
+struct SecondBuckets {
+    start: Instant,
+    counts: Vec<AtomicI32>,
+}
+
+impl SecondBuckets {
+    fn new(start: Instant, duration: Duration) -> SecondBuckets {
+        // Requests that are in flight at the end may complete up to CLIENT_TIMEOUT later.
+        let len = (duration + CLIENT_TIMEOUT).as_secs() as usize + 1;
+        SecondBuckets {
+            start,
+            counts: (0..len).map(|_| AtomicI32::new(0)).collect(),
+        }
+    }
+
+    fn record(&self, at: Instant) {
+        let index = at.duration_since(self.start).as_secs() as usize;
+        if let Some(count) = self.counts.get(index) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn to_vec(&self) -> Vec<i32> {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+/// Returns the full seconds of the measurement window.
+/// Without an explicit window start, the first second is dropped because the tasks are still starting.
+fn window_seconds(counts: &[i32], window: MeasurementWindow) -> &[i32] {
+    let end = (window.end.as_secs() as usize).clamp(1, counts.len());
+    let first = match window.start.is_zero() {
+        true => 1,
+        false => window.start.as_secs_f64().ceil() as usize,
+    };
+    &counts[first.min(end - 1)..end]
+}
+
+/// Derives an independent seed from `seed` and `index` (SplitMix64).
+pub fn derive_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// The order in which a task sends the requests, as indices into the requests.
+/// Without route weights, it cycles through all requests in random order.
+/// With route weights, it draws a route according to its weight, then a random request of this route.
+struct RequestSequence {
+    routes: Vec<Vec<usize>>,
+    distribution: Option<WeightedIndex<u32>>,
+    index: usize,
+    rng: StdRng,
+}
+
+impl RequestSequence {
+    fn new(
+        requests: &[PreparedHttpRequest],
+        route_weights: Option<&IndexMap<String, u32>>,
+        seed: u64,
+    ) -> RequestSequence {
+        let mut rng = StdRng::seed_from_u64(seed);
+        match route_weights {
+            None => {
+                let mut indices: Vec<usize> = (0..requests.len()).collect();
+                indices.shuffle(&mut rng);
+                RequestSequence {
+                    routes: vec![indices],
+                    distribution: None,
+                    index: 0,
+                    rng,
+                }
+            }
+            Some(route_weights) => {
+                let mut routes: Vec<Vec<usize>> = Vec::new();
+                let mut weights: Vec<u32> = Vec::new();
+                for (route, weight) in route_weights {
+                    let route_requests: Vec<usize> = (0..requests.len())
+                        .filter(|index| &requests[*index].route == route)
+                        .collect();
+                    if route_requests.is_empty() {
+                        panic!("Route weight for unknown route: {}", route);
+                    }
+                    routes.push(route_requests);
+                    weights.push(*weight);
+                }
+                RequestSequence {
+                    routes,
+                    distribution: Some(WeightedIndex::new(weights).expect("Invalid route weights")),
+                    index: 0,
+                    rng,
+                }
+            }
+        }
+    }
+
+    fn next(&mut self) -> usize {
+        match &self.distribution {
+            None => {
+                let indices = &self.routes[0];
+                let index = indices[self.index % indices.len()];
+                self.index += 1;
+                index
+            }
+            Some(distribution) => {
+                let route = &self.routes[distribution.sample(&mut self.rng)];
+                route[self.rng.random_range(0..route.len())]
+            }
+        }
+    }
+}
+
+/// The first `count` requests of the request order with the given seed, as indices into the requests.
+/// Used to hand the request order to external load tools.
+pub fn request_order(
+    requests: &[PreparedHttpRequest],
+    route_weights: Option<&IndexMap<String, u32>>,
+    seed: u64,
+    count: usize,
+) -> Vec<usize> {
+    let mut sequence = RequestSequence::new(requests, route_weights, seed);
+    (0..count).map(|_| sequence.next()).collect()
+}
+
+/// Connections shared by all tasks.
+/// HTTP/1.1 connections are lent to one request at a time, the most recently used first,
+/// so the number of open connections follows the number of requests in flight (e.g. during the ramp-up).
+/// With more connections than requests in flight, they are lent in turns instead, so that all of them are used.
+/// HTTP/2 connections are used in turns by any number of concurrent streams.
+struct ConnectionPool<E: LoadEngine> {
+    connections: Vec<E>,
+    exclusive: bool,
+    rotate: bool,
+    idle: Mutex<VecDeque<usize>>,
+    idle_count: Semaphore,
+    next: AtomicUsize,
+}
+
+/// A connection of the pool that is returned when dropped.
+struct PooledConnection<'a, E: LoadEngine> {
+    pool: &'a ConnectionPool<E>,
+    index: usize,
+    _permit: Option<SemaphorePermit<'a>>,
+}
+
+impl<E: LoadEngine> ConnectionPool<E> {
+    fn new(
+        protocol: Protocol,
+        connection_mode: ConnectionMode,
+        connections: usize,
+        max_in_flight: usize,
+    ) -> ConnectionPool<E> {
+        let connections = connections.max(1);
+        ConnectionPool {
+            connections: (0..connections)
+                .map(|_| E::new(protocol, connection_mode))
+                .collect(),
+            exclusive: protocol == Protocol::Http1,
+            rotate: connections > max_in_flight,
+            // Idle connections, the next one at the front.
+            idle: Mutex::new((0..connections).collect()),
+            idle_count: Semaphore::new(connections),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    async fn acquire(&self) -> PooledConnection<'_, E> {
+        if !self.exclusive {
+            let index = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+            return PooledConnection {
+                pool: self,
+                index,
+                _permit: None,
+            };
+        }
+
+        let permit = self
+            .idle_count
+            .acquire()
+            .await
+            .expect("Connection pool closed");
+        let index = self
+            .idle
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("No idle connection despite permit");
+        PooledConnection {
+            pool: self,
+            index,
+            _permit: Some(permit),
+        }
+    }
+}
+
+impl<E: LoadEngine> PooledConnection<'_, E> {
+    fn engine(&self) -> &E {
+        &self.pool.connections[self.index]
+    }
+}
+
+impl<E: LoadEngine> Drop for PooledConnection<'_, E> {
+    fn drop(&mut self) {
+        // The permit is released after this, so the connection is idle again before it can be acquired.
+        if self.pool.exclusive {
+            let mut idle = self.pool.idle.lock().unwrap();
+            match self.pool.rotate {
+                true => idle.push_back(self.index),
+                false => idle.push_front(self.index),
+            }
+        }
+    }
+}
+
+/// Validation state shared between all tasks.
+struct ValidationState {
+    invalid_count: AtomicUsize,
+    aborted: AtomicBool,
+    kept: Mutex<Vec<InvalidResponse>>,
+}
+
+#[derive(Clone, Default)]
 pub struct PreparedHttpRequest {
+    /// Label used to group the results, e.g. "element".
+    pub route: String,
+    pub method: Method,
     pub url: String,
-    pub expected_response: HashMap<String, SerializedValue>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+
+    /// Responses with another status count as failed.
+    pub expected_status: StatusCode,
+
+    /// Responses are only validated if there is an expectation.
+    pub expected_response: Option<Expectation>,
+
+    /// Part of the response that depends on the data version of the web data source.
+    pub rotated_response: Option<RotatedExpectation>,
+    pub expected_headers: HeaderExpectation,
+}
+
+fn has_expectations(request: &PreparedHttpRequest) -> bool {
+    request.expected_response.is_some()
+        || request.rotated_response.is_some()
+        || !request.expected_headers.is_empty()
+}
+
+/// Checks the headers and then the body of a sampled response.
+fn validate_response(
+    request: &PreparedHttpRequest,
+    headers: &HeaderMap,
+    response: &PendingValidationResponse,
+    request_validator: RequestValidatorFn,
+    sent_at: SystemTime,
+) -> Result<(), String> {
+    request.expected_headers.check(headers)?;
+    if let Some(rotated) = &request.rotated_response {
+        let json: serde_json::Value = serde_json::from_str(&response.body)
+            .map_err(|_| format!("Failed to parse JSON: {}", response.body))?;
+        rotated.check(&json, sent_at, SystemTime::now())?;
+    }
+    request_validator(response)
+}
+
+fn header_list(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
 }
 
 pub struct PendingValidationResponse<'a> {
     pub url: &'a str,
     pub body: String,
-    pub expected_body: &'a HashMap<String, SerializedValue>,
+    pub expected_body: Option<&'a Expectation>,
 }
 
 type RequestValidatorFn = fn(&PendingValidationResponse) -> Result<(), String>;
 
 pub fn run_http_load_test(
-    concurrency: usize,
-    duration: Duration,
-    requests: &Vec<PreparedHttpRequest>,
+    config: &HttpLoadConfig,
+    requests: &[PreparedHttpRequest],
     request_validator: RequestValidatorFn,
-    verbose: bool,
 ) -> HttpLoadResult {
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    let result = rt.block_on(run_load_test(
-        concurrency,
-        duration,
-        requests,
-        request_validator,
-        verbose,
-    ));
-    result
+    if config.engine.is_external() {
+        return run_external_load_test(config, requests, request_validator);
+    }
+
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.worker_threads(config.worker_threads).enable_all();
+    if let Some(cpuset) = config.cpuset.clone() {
+        builder.on_thread_start(move || cpuset.pin_current_thread());
+    }
+    let rt = builder.build().unwrap();
+    match config.engine {
+        Engine::Reqwest => rt.block_on(run_load_test::<ReqwestEngine>(
+            config,
+            requests,
+            request_validator,
+        )),
+        Engine::Hyper => rt.block_on(run_load_test::<HyperEngine>(
+            config,
+            requests,
+            request_validator,
+        )),
+        Engine::Wrk | Engine::Oha | Engine::K6 => unreachable!(),
+    }
 }
 
-async fn run_load_test(
-    concurrency: usize,
-    duration: Duration,
-    requests: &Vec<PreparedHttpRequest>,
+async fn run_load_test<E: LoadEngine>(
+    config: &HttpLoadConfig,
+    requests: &[PreparedHttpRequest],
     request_validator: RequestValidatorFn,
-    verbose: bool,
 ) -> HttpLoadResult {
+    let max_in_flight = config.max_in_flight;
+    let window = config.measurement_window();
+    let total_duration = config.total_duration();
+    let ramp_up = config.ramp_up;
+    let verbose = config.verbose;
+
+    // In open-loop mode, every task sends at 1/max_in_flight of the target rate.
+    let send_interval: Option<Duration> = match config.load_mode {
+        LoadMode::Closed => None,
+        LoadMode::Open => {
+            let target_rps = config
+                .target_rps
+                .expect("target_rps is required in open-loop mode");
+            Some(Duration::from_secs_f64(
+                max_in_flight as f64 / target_rps as f64,
+            ))
+        }
+    };
+
+    let validation = config.validation.with_error_budget(config.error_budget);
+    let validation_state = Arc::new(ValidationState {
+        invalid_count: AtomicUsize::new(0),
+        aborted: AtomicBool::new(false),
+        kept: Mutex::new(Vec::with_capacity(validation.max_kept_invalid)),
+    });
+
+    // Building the clients takes a while, so this is done before the clock starts.
+    let pool = Arc::new(ConnectionPool::<E>::new(
+        config.protocol,
+        config.connection_mode,
+        config.connections,
+        config.max_in_flight,
+    ));
+    let prepared_requests: Arc<Vec<E::Request>> = Arc::new(
+        requests
+            .iter()
+            .map(|request| E::prepare(request, config.protocol, config.connection_mode))
+            .collect(),
+    );
+    let shared_requests: Arc<Vec<PreparedHttpRequest>> = Arc::new(requests.to_vec());
+    let recorder: Option<Arc<TraceRecorder>> = config
+        .trace_path
+        .as_deref()
+        .map(|path| Arc::new(TraceRecorder::create(path)));
+
+    // All tasks share the same clock so that the seconds are aligned.
+    let start = Instant::now();
+    let start_time = SystemTime::now();
+    let second_buckets = Arc::new(SecondBuckets::new(start, total_duration));
+
+    let mut route_stats: IndexMap<String, RouteStats> = IndexMap::new();
+    for request in requests {
+        if !route_stats.contains_key(&request.route) {
+            route_stats.insert(request.route.clone(), RouteStats::new());
+        }
+    }
+
     let mut handles: Vec<JoinHandle<ThreadResult>> = Vec::new();
 
-    for _ in 0..concurrency {
-        let mut requests_clone = requests.clone();
-        requests_clone.shuffle(&mut rand::rng());
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak_in_flight = Arc::new(AtomicUsize::new(0));
+
+    // Index of the next request of the schedule, shared by all tasks.
+    let schedule_cursor = Arc::new(AtomicUsize::new(0));
+
+    for task_index in 0..max_in_flight {
+        let pool = Arc::clone(&pool);
+        let prepared_requests = Arc::clone(&prepared_requests);
+        let requests = Arc::clone(&shared_requests);
+        let in_flight = Arc::clone(&in_flight);
+        let peak_in_flight = Arc::clone(&peak_in_flight);
+        let schedule = config.schedule.clone();
+        let schedule_cursor = Arc::clone(&schedule_cursor);
+        let recorder = recorder.clone();
+        let task_seed = derive_seed(config.seed, task_index as u64);
+        let mut sequence =
+            RequestSequence::new(&requests, config.route_weights.as_ref(), task_seed);
+        let validation = validation.clone();
+        let validation_state = Arc::clone(&validation_state);
+        let second_buckets = Arc::clone(&second_buckets);
+        let mut local_route_stats = route_stats.clone();
 
         let handle = task::spawn(async move {
             let mut local_success_count = 0;
-            let mut success_count_temp = 0;
-            let mut local_fail_count = 0;
-            let mut local_latency_us: Vec<u64> = Vec::with_capacity(1_000_000);
-            let mut rps_per_second: Vec<i32> = Vec::with_capacity(100);
-            let mut responses: Vec<PendingValidationResponse> = Vec::with_capacity(1_000_000);
-
-            let client = reqwest::Client::builder()
-                .timeout(Duration::from_secs(15))
-                .build()
-                .unwrap();
-            let start = std::time::Instant::now();
-            let mut second = 1;
-            'outer: loop {
-                for request in &requests_clone {
-                    let url = &request.url;
-                    let request_start = std::time::Instant::now();
-                    match client.get(url).send().await {
-                        Ok(response) => {
-                            let status = &response.status();
-                            let body = response.text().await.unwrap();
+            let mut local_errors = ErrorCounts::default();
+            let mut local_sent_count = 0;
+            let mut window_sent_count = 0;
+            let mut local_latency_us = Histogram::new();
+            let mut local_ttfb_us = Histogram::new();
+            let mut local_body_bytes: u64 = 0;
+            let mut sample_rng = StdRng::seed_from_u64(derive_seed(task_seed, 0));
+            let mut record_rng = StdRng::seed_from_u64(derive_seed(task_seed, 1));
+
+            let task_start = start + ramp_up.mul_f64(task_index as f64 / max_in_flight as f64);
+            time::sleep_until(time::Instant::from_std(task_start)).await;
+
+            // Stagger the schedules of the tasks so that the requests are evenly spread.
+            let mut next_send = match send_interval {
+                Some(interval) => {
+                    task_start + interval.mul_f64(task_index as f64 / max_in_flight as f64)
+                }
+                None => task_start,
+            };
+
+            loop {
+                if validation_state.aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let (index, request_start) = match (&schedule, send_interval) {
+                    (Some(schedule), _) => {
+                        let index = schedule_cursor.fetch_add(1, Ordering::Relaxed);
+                        if index >= schedule.len() {
+                            break;
+                        }
+                        // Latency is measured from the recorded send time, even if we are late.
+                        let intended = start + schedule[index];
+                        time::sleep_until(time::Instant::from_std(intended)).await;
+                        (index, intended)
+                    }
+                    (None, Some(interval)) => {
+                        // Latency is measured from the intended send time, even if we are late.
+                        let intended = next_send;
+                        next_send += interval;
+                        if intended.duration_since(start) >= total_duration {
+                            break;
+                        }
+                        time::sleep_until(time::Instant::from_std(intended)).await;
+                        (sequence.next(), intended)
+                    }
+                    (None, None) => (sequence.next(), Instant::now()),
+                };
+                let request = &requests[index];
+                let url = &request.url;
+                let in_window = window.contains(request_start.duration_since(start));
+                local_sent_count += 1;
+                if in_window {
+                    window_sent_count += 1;
+                }
+                let response = {
+                    // Waiting for a free connection counts towards the latency.
+                    let connection = pool.acquire().await;
+                    let current = in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+                    peak_in_flight.fetch_max(current, Ordering::Relaxed);
+                    let response = connection
+                        .engine()
+                        .send(&prepared_requests[index])
+                        .await
+                        .map(|response| {
                             let latency_us = request_start.elapsed().as_micros() as u64;
-                            if *status == StatusCode::OK {
-                                local_success_count += 1;
-                                local_latency_us.push(latency_us);
-                                responses.push(PendingValidationResponse {
-                                    url,
-                                    body,
-                                    expected_body: &request.expected_response,
-                                });
-                            } else {
-                                local_fail_count += 1;
-                                if verbose {
-                                    println!("Unexpected response {} for {}", *status, url);
-                                    println!(
-                                        "Success: {}, Fail: {}",
-                                        local_success_count, local_fail_count
-                                    );
+                            let ttfb_us = response
+                                .headers_received
+                                .duration_since(request_start)
+                                .as_micros() as u64;
+                            (
+                                response.status,
+                                ttfb_us,
+                                latency_us,
+                                response.headers,
+                                response.body,
+                            )
+                        });
+                    in_flight.fetch_sub(1, Ordering::Relaxed);
+                    response
+                };
+                let route = local_route_stats.get_mut(&request.route).unwrap();
+                let mut recorded = RecordedResponse::default();
+                match response {
+                    Ok((status, ttfb_us, latency_us, headers, body)) => {
+                        recorded.status = Some(status.as_u16());
+                        recorded.latency_us = Some(latency_us);
+                        recorded.ttfb_us = Some(ttfb_us);
+                        if status == request.expected_status {
+                            let body_bytes = body.len() as u64;
+                            let sampled = has_expectations(request)
+                                && (validation.sample_rate >= 1.0
+                                    || sample_rng.random::<f64>() < validation.sample_rate);
+                            // Only sampled or recorded bodies are converted to text.
+                            let validation_result = match sampled {
+                                true => {
+                                    let response = PendingValidationResponse {
+                                        url,
+                                        body: String::from_utf8_lossy(&body).into_owned(),
+                                        expected_body: request.expected_response.as_ref(),
+                                    };
+                                    validate_response(
+                                        request,
+                                        &headers,
+                                        &response,
+                                        request_validator,
+                                        start_time + request_start.duration_since(start),
+                                    )
+                                    .map_err(|e| (e, response))
                                 }
+                                false => Ok(()),
+                            };
+                            if route.headers.is_none() {
+                                route.headers = Some(header_list(&headers));
                             }
-                        }
-                        Err(e) => {
-                            if e.is_timeout() {
-                                // ignore timeout errors
-                            } else {
-                                if verbose {
-                                    println!(
-                                        "Request to {} failed: {}",
-                                        url,
-                                        report_reqwest_error(&e)
-                                    );
-                                    println!(
-                                        "Success: {}, Fail: {}",
-                                        local_success_count, local_fail_count
+                            match validation_result {
+                                Ok(()) => {
+                                    local_success_count += 1;
+                                    route.success_count += 1;
+                                    if in_window {
+                                        local_latency_us.record(latency_us);
+                                        local_ttfb_us.record(ttfb_us);
+                                        local_body_bytes += body_bytes;
+                                        route.latency_us.record(latency_us);
+                                        route.ttfb_us.record(ttfb_us);
+                                        route.body_bytes += body_bytes;
+                                    }
+                                    second_buckets.record(Instant::now());
+                                    if recorder.is_some()
+                                        && record_rng.random::<f64>() < RECORDED_BODY_RATE
+                                    {
+                                        recorded.body =
+                                            Some(String::from_utf8_lossy(&body).into_owned());
+                                    }
+                                }
+                                Err((e, response)) => {
+                                    local_errors.invalid += 1;
+                                    route.fail_count += 1;
+                                    if recorder.is_some() {
+                                        recorded.outcome = Outcome::Invalid;
+                                        recorded.error = Some(e.clone());
+                                        recorded.body = Some(response.body.clone());
+                                    }
+                                    report_invalid_response(
+                                        &response,
+                                        e,
+                                        &validation,
+                                        &validation_state,
+                                        verbose,
                                     );
                                 }
-                                local_fail_count += 1;
+                            }
+                        } else {
+                            local_errors.status += 1;
+                            route.fail_count += 1;
+                            if recorder.is_some() {
+                                recorded.outcome = Outcome::Status;
+                                recorded.body = Some(String::from_utf8_lossy(&body).into_owned());
+                            }
+                            if verbose {
+                                println!("Unexpected response {} for {}", status, url);
+                                println!(
+                                    "Success: {}, Fail: {}",
+                                    local_success_count,
+                                    local_errors.total()
+                                );
                             }
                         }
                     }
-
-                    let elapsed = start.elapsed().as_secs();
-                    if elapsed >= second {
-                        if elapsed >= duration.as_secs() {
-                            break 'outer;
-                        } else {
-                            rps_per_second.push(local_success_count - success_count_temp);
-                            success_count_temp = local_success_count;
-                            second += 1;
+                    Err(e) => {
+                        local_errors.record_request_error(&e);
+                        route.fail_count += 1;
+                        if recorder.is_some() {
+                            recorded.outcome = Outcome::Error;
+                            recorded.error = Some(report_request_error(&e));
+                        }
+                        if verbose {
+                            println!("Request to {} failed: {}", url, report_request_error(&e));
+                            println!(
+                                "Success: {}, Fail: {}",
+                                local_success_count,
+                                local_errors.total()
+                            );
                         }
                     }
                 }
-            }
 
-            // validate
-            for response in responses {
-                if let Err(e) = request_validator(&response) {
-                    local_success_count -= 1;
-                    local_fail_count += 1;
-                    if verbose {
-                        println!(
-                            "Validation failed for response for {}: {}, expected: {:?}, {}",
-                            response.url, response.body, response.expected_body, e
-                        );
-                    }
+                if let Some(recorder) = &recorder {
+                    recorder.record(request, request_start.duration_since(start), recorded);
+                }
+
+                // A replay ends when all requests have been sent.
+                if schedule.is_none() && start.elapsed() >= total_duration {
+                    break;
                 }
             }
 
             ThreadResult {
                 success_count: local_success_count,
-                fail_count: local_fail_count,
+                errors: local_errors,
+                sent_count: local_sent_count,
+                window_sent_count,
                 latency_us: local_latency_us,
-                rps_per_second,
+                ttfb_us: local_ttfb_us,
+                body_bytes: local_body_bytes,
+                route_stats: local_route_stats,
                 total_time: Duration::from_millis(start.elapsed().as_millis() as u64),
             }
         });
@@ -167,12 +1055,13 @@ async fn run_load_test(
         handles.push(handle);
     }
 
-    time::sleep(duration).await;
-
     let mut handle_results: Vec<ThreadResult> = Vec::new();
     for handle in handles.into_iter() {
         handle_results.push(handle.await.unwrap());
     }
+    if let Some(recorder) = &recorder {
+        recorder.flush();
+    }
 
     // max time of all threads
     let total_time = handle_results
@@ -188,65 +1077,339 @@ async fn run_load_test(
     let success_count = handle_results
         .iter()
         .fold(0, |acc, x| acc + x.success_count);
-    let fail_count = handle_results.iter().fold(0, |acc, x| acc + x.fail_count);
+    let errors = handle_results
+        .iter()
+        .fold(ErrorCounts::default(), |mut acc, x| {
+            acc.merge(&x.errors);
+            acc
+        });
+    let fail_count = errors.total();
+    let sent_count = handle_results.iter().fold(0, |acc, x| acc + x.sent_count);
+    let window_sent_count = handle_results
+        .iter()
+        .fold(0, |acc, x| acc + x.window_sent_count);
 
     if success_count == 0 {
         panic!("No successful requests. Something is wrong. Run with --verbose to see the errors.");
     }
 
-    if fail_count > 0 {
-        panic!("Some requests failed. Run with --verbose to see the errors.");
-    }
+    check_error_budget(config, &errors, sent_count, &validation_state);
 
-    let rps_per_second: Vec<i32> = {
-        let all_vectors: Vec<Vec<i32>> = handle_results
-            .iter()
-            .map(|x| x.rps_per_second.clone())
-            .collect();
-        let mut rps_per_second: Vec<i32> = Vec::new();
-        for i in 0..all_vectors[0].len() {
-            let mut sum = 0;
-            for vector in &all_vectors {
-                sum += vector[i];
-            }
-            rps_per_second.push(sum);
-        }
+    let rps_per_second: Vec<i32> = window_seconds(&second_buckets.to_vec(), window).to_vec();
+    let rps_per_second_sorted: Vec<i32> = {
+        let mut rps_per_second = rps_per_second.clone();
         rps_per_second.sort();
         rps_per_second
     };
-    let latency_us: Vec<u64> = {
-        let mut latency_us: Vec<u64> = handle_results.iter().fold(Vec::new(), |mut acc, x| {
-            acc.extend(x.latency_us.clone());
-            acc
-        });
-        latency_us.sort();
+    let latency_us: Histogram = {
+        let mut latency_us = Histogram::new();
+        for result in &handle_results {
+            latency_us.merge(&result.latency_us);
+        }
         latency_us
     };
+    let ttfb_us: Histogram = {
+        let mut ttfb_us = Histogram::new();
+        for result in &handle_results {
+            ttfb_us.merge(&result.ttfb_us);
+        }
+        ttfb_us
+    };
+    let body_bytes = handle_results.iter().fold(0, |acc, x| acc + x.body_bytes);
+
+    for result in &handle_results {
+        for (route, stats) in &result.route_stats {
+            route_stats.get_mut(route).unwrap().merge(stats);
+        }
+    }
+    let routes: IndexMap<String, RouteResult> = route_stats
+        .into_iter()
+        .map(|(route, stats)| {
+            let percentile = |histogram: &Histogram, p: f64| match histogram.is_empty() {
+                true => Duration::ZERO,
+                false => Duration::from_micros(histogram.percentile(p)),
+            };
+            let route_result = RouteResult {
+                success_count: stats.success_count,
+                fail_count: stats.fail_count,
+                rps_share: match latency_us.count() {
+                    0 => 0,
+                    window_count => (stats.latency_us.count() * 100 / window_count) as i32,
+                },
+                latency_median: percentile(&stats.latency_us, 0.5),
+                latency_p90: percentile(&stats.latency_us, 0.9),
+                latency_p99: percentile(&stats.latency_us, 0.99),
+                latency_p999: percentile(&stats.latency_us, 0.999),
+                ttfb_median: percentile(&stats.ttfb_us, 0.5),
+                ttfb_p99: percentile(&stats.ttfb_us, 0.99),
+                bytes_per_response: bytes_per_response(stats.body_bytes, &stats.latency_us),
+                headers: stats.headers.unwrap_or_default(),
+            };
+            (route, route_result)
+        })
+        .collect();
 
     HttpLoadResult {
         success_count,
         fail_count,
+        errors,
         total_time,
-        rps_median: percentile::p50(&rps_per_second),
-        rps_p99: percentile::p1(&rps_per_second), // inverse because we want the worst case
-        latency_median: Duration::from_micros(percentile::p50(&latency_us)),
-        latency_p99: Duration::from_micros(percentile::p99(&latency_us)),
+        rps_median: percentile::p50(&rps_per_second_sorted),
+        rps_p99: percentile::p1(&rps_per_second_sorted), // inverse because we want the worst case
+        latency_median: Duration::from_micros(latency_us.p50()),
+        latency_p90: Duration::from_micros(latency_us.p90()),
+        latency_p99: Duration::from_micros(latency_us.p99()),
+        latency_p999: Duration::from_micros(latency_us.p999()),
+        latency_max: Duration::from_micros(latency_us.max()),
+        ttfb_median: Duration::from_micros(ttfb_us.p50()),
+        ttfb_p90: Duration::from_micros(ttfb_us.p90()),
+        ttfb_p99: Duration::from_micros(ttfb_us.p99()),
+        ttfb_p999: Duration::from_micros(ttfb_us.p999()),
+        bytes_per_response: bytes_per_response(body_bytes, &latency_us),
+        target_rps: match config.load_mode {
+            LoadMode::Closed => None,
+            LoadMode::Open => config.target_rps.map(|rps| rps as i32),
+        },
+        achieved_rps: (window_sent_count as f64 / config.duration.as_secs_f64()) as i32,
+        latency_histogram: latency_us,
+        rps_per_second,
+        measurement_window: window,
+        connections: pool.connections.len(),
+        max_in_flight,
+        peak_in_flight: peak_in_flight.load(Ordering::Relaxed),
+        routes,
     }
 }
 
 struct ThreadResult {
     success_count: i32,
-    fail_count: i32,
+    errors: ErrorCounts,
+
+    /// Number of requests that have been sent, regardless of their outcome.
+    sent_count: i32,
+
+    /// Number of requests that have been sent within the measurement window.
+    window_sent_count: i32,
 
-    /// After each second, the number of requests is written to this vector.
-    /// Used to calculate P50 and P99.
-    rps_per_second: Vec<i32>,
+    latency_us: Histogram,
+    ttfb_us: Histogram,
 
-    latency_us: Vec<u64>,
+    /// Body bytes of the successful responses within the measurement window.
+    body_bytes: u64,
+    route_stats: IndexMap<String, RouteStats>,
     total_time: Duration,
 }
 
-fn report_reqwest_error(err: &reqwest::Error) -> String {
+/// Average body size of the responses recorded in `latency_us`.
+fn bytes_per_response(body_bytes: u64, latency_us: &Histogram) -> i32 {
+    match latency_us.count() {
+        0 => 0,
+        count => (body_bytes / count) as i32,
+    }
+}
+
+/// Panics if the validation has been aborted or more requests have failed than the error budget allows.
+fn check_error_budget(
+    config: &HttpLoadConfig,
+    errors: &ErrorCounts,
+    sent_count: i32,
+    validation_state: &ValidationState,
+) {
+    if validation_state.aborted.load(Ordering::Relaxed) {
+        panic!(
+            "Aborted after {} invalid responses ({}):\n{}",
+            validation_state.invalid_count.load(Ordering::Relaxed),
+            config.seed_info(),
+            format_invalid_responses(&validation_state.kept.lock().unwrap()),
+        );
+    }
+
+    if errors.total() as f64 > config.error_budget * sent_count as f64 {
+        let kept = validation_state.kept.lock().unwrap();
+        if kept.is_empty() {
+            panic!(
+                "{} of {} requests failed ({}), error budget is {} ({}). Run with --verbose to see the errors.",
+                errors.total(), sent_count, errors, config.error_budget, config.seed_info()
+            );
+        }
+        panic!(
+            "{} of {} requests failed ({}), error budget is {} ({}). Invalid responses:\n{}",
+            errors.total(),
+            sent_count,
+            errors,
+            config.error_budget,
+            config.seed_info(),
+            format_invalid_responses(&kept),
+        );
+    }
+}
+
+/// Result of sending every request once outside of a load test.
+pub struct SampleValidation {
+    pub errors: ErrorCounts,
+
+    /// Average body size of the successful responses in bytes.
+    pub bytes_per_response: i32,
+}
+
+/// Sends every request once and validates a sample of the responses like the load test does.
+/// Used for external load tools, which do not validate the responses themselves.
+/// Panics if more requests have failed than the error budget allows.
+pub fn validate_sample(
+    config: &HttpLoadConfig,
+    requests: &[PreparedHttpRequest],
+    request_validator: RequestValidatorFn,
+) -> SampleValidation {
+    let validation = &config.validation.with_error_budget(config.error_budget);
+    let validation_state = ValidationState {
+        invalid_count: AtomicUsize::new(0),
+        aborted: AtomicBool::new(false),
+        kept: Mutex::new(Vec::with_capacity(validation.max_kept_invalid)),
+    };
+    let client = reqwest::blocking::Client::builder()
+        .timeout(CLIENT_TIMEOUT)
+        .build()
+        .unwrap();
+    let mut sample_rng = StdRng::seed_from_u64(config.seed);
+    let mut errors = ErrorCounts::default();
+    let mut success_count: u64 = 0;
+    let mut body_bytes: u64 = 0;
+    let mut sent_count = 0;
+
+    for request in requests {
+        if validation_state.aborted.load(Ordering::Relaxed) {
+            break;
+        }
+
+        sent_count += 1;
+        let mut builder = client.request(request.method.clone(), &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        let sent_at = SystemTime::now();
+        let response = builder.send().and_then(|response| {
+            let status = response.status();
+            let headers = response.headers().clone();
+            response.bytes().map(|body| (status, headers, body))
+        });
+        match response {
+            Ok((status, headers, body)) if status == request.expected_status => {
+                let sampled = has_expectations(request)
+                    && (validation.sample_rate >= 1.0
+                        || sample_rng.random::<f64>() < validation.sample_rate);
+                let validation_result = match sampled {
+                    true => {
+                        let response = PendingValidationResponse {
+                            url: &request.url,
+                            body: String::from_utf8_lossy(&body).into_owned(),
+                            expected_body: request.expected_response.as_ref(),
+                        };
+                        validate_response(request, &headers, &response, request_validator, sent_at)
+                            .map_err(|e| (e, response))
+                    }
+                    false => Ok(()),
+                };
+                match validation_result {
+                    Ok(()) => {
+                        success_count += 1;
+                        body_bytes += body.len() as u64;
+                    }
+                    Err((e, response)) => {
+                        errors.invalid += 1;
+                        report_invalid_response(
+                            &response,
+                            e,
+                            validation,
+                            &validation_state,
+                            config.verbose,
+                        );
+                    }
+                }
+            }
+            Ok((status, _, _)) => {
+                errors.status += 1;
+                if config.verbose {
+                    println!("Unexpected response {} for {}", status, request.url);
+                }
+            }
+            Err(e) => {
+                let e = RequestError::Reqwest(e);
+                errors.record_request_error(&e);
+                if config.verbose {
+                    println!(
+                        "Request to {} failed: {}",
+                        request.url,
+                        report_request_error(&e)
+                    );
+                }
+            }
+        }
+    }
+
+    check_error_budget(config, &errors, sent_count, &validation_state);
+
+    SampleValidation {
+        errors,
+        bytes_per_response: match success_count {
+            0 => 0,
+            count => (body_bytes / count) as i32,
+        },
+    }
+}
+
+/// Counts an invalid response, keeps it for the report if there is space left,
+/// and aborts the load test if the fail-fast threshold has been reached.
+fn report_invalid_response(
+    response: &PendingValidationResponse,
+    reason: String,
+    validation: &ValidationConfig,
+    state: &ValidationState,
+    verbose: bool,
+) {
+    if verbose {
+        println!(
+            "Validation failed for response for {}: {}, expected: {:?}, {}",
+            response.url, response.body, response.expected_body, reason
+        );
+    }
+
+    let invalid_count = state.invalid_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+    {
+        let mut kept = state.kept.lock().unwrap();
+        if kept.len() < validation.max_kept_invalid {
+            kept.push(InvalidResponse {
+                url: response.url.to_string(),
+                expected: serde_json::to_string(&response.expected_body).unwrap(),
+                actual: response.body.chars().take(MAX_KEPT_BODY_LENGTH).collect(),
+                reason,
+            });
+        }
+    }
+
+    if let Some(threshold) = validation.fail_fast_threshold {
+        if invalid_count >= threshold {
+            state.aborted.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn format_invalid_responses(responses: &[InvalidResponse]) -> String {
+    let mut s = String::new();
+    for response in responses {
+        let _ = write!(
+            s,
+            "\n{}\n  - expected: {}\n  + actual:   {}\n  reason: {}\n",
+            response.url, response.expected, response.actual, response.reason
+        );
+    }
+    s
+}
+
+fn report_request_error(err: &RequestError) -> String {
     let mut s = format!("{}", err);
     if let Some(src) = err.source() {
         let _ = write!(s, "\n\nCaused by: {}", src);
@@ -260,3 +1423,206 @@ fn report_reqwest_error(err: &reqwest::Error) -> String {
 
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod window_seconds {
+        use super::*;
+
+        fn window(start_ms: u64, end_ms: u64) -> MeasurementWindow {
+            MeasurementWindow {
+                start: Duration::from_millis(start_ms),
+                end: Duration::from_millis(end_ms),
+            }
+        }
+
+        #[test]
+        fn should_drop_first_and_trailing_seconds() {
+            let counts = vec![50, 100, 110, 105, 30, 2, 0];
+            assert_eq!(window_seconds(&counts, window(0, 4000)), &[100, 110, 105]);
+        }
+
+        #[test]
+        fn should_ignore_sub_second_remainder() {
+            let counts = vec![50, 100, 110, 20];
+            assert_eq!(window_seconds(&counts, window(0, 2500)), &[100]);
+        }
+
+        #[test]
+        fn should_keep_first_second_if_only_one() {
+            let counts = vec![50, 3];
+            assert_eq!(window_seconds(&counts, window(0, 1000)), &[50]);
+        }
+
+        #[test]
+        fn should_start_at_explicit_window_start() {
+            let counts = vec![10, 20, 30, 100, 110, 105, 30];
+            assert_eq!(
+                window_seconds(&counts, window(3000, 6000)),
+                &[100, 110, 105]
+            );
+        }
+
+        #[test]
+        fn should_skip_partial_second_at_window_start() {
+            let counts = vec![10, 20, 30, 100, 110, 105, 30];
+            assert_eq!(
+                window_seconds(&counts, window(2500, 6000)),
+                &[100, 110, 105]
+            );
+        }
+    }
+
+    mod request_sequence {
+        use super::*;
+
+        fn request(route: &str, url: &str) -> PreparedHttpRequest {
+            PreparedHttpRequest {
+                route: route.to_string(),
+                url: url.to_string(),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn should_cycle_through_all_requests_without_weights() {
+            let requests = vec![request("a", "1"), request("a", "2"), request("b", "3")];
+            let mut sequence = RequestSequence::new(&requests, None, 1);
+            let mut urls: Vec<String> = (0..6)
+                .map(|_| requests[sequence.next()].url.clone())
+                .collect();
+            assert_eq!(urls[0..3], urls[3..6]);
+            urls.truncate(3);
+            urls.sort();
+            assert_eq!(urls, vec!["1", "2", "3"]);
+        }
+
+        #[test]
+        fn should_draw_routes_according_to_weights() {
+            let requests = vec![request("a", "1"), request("a", "2"), request("b", "3")];
+            let weights = IndexMap::from([("a".to_string(), 9), ("b".to_string(), 1)]);
+            let mut sequence = RequestSequence::new(&requests, Some(&weights), 1);
+            let count_a = (0..10_000)
+                .filter(|_| requests[sequence.next()].route == "a")
+                .count();
+            assert!((8_700..9_300).contains(&count_a), "{count_a}");
+        }
+
+        #[test]
+        #[should_panic(expected = "unknown route")]
+        fn should_reject_unknown_route() {
+            let requests = vec![request("a", "1")];
+            let weights = IndexMap::from([("c".to_string(), 1)]);
+            RequestSequence::new(&requests, Some(&weights), 1);
+        }
+
+        #[test]
+        fn should_repeat_order_with_same_seed() {
+            let requests: Vec<PreparedHttpRequest> =
+                (0..100).map(|i| request("a", &i.to_string())).collect();
+            let urls = |seed: u64| {
+                let mut sequence = RequestSequence::new(&requests, None, seed);
+                (0..100)
+                    .map(|_| requests[sequence.next()].url.clone())
+                    .collect::<Vec<String>>()
+            };
+            assert_eq!(urls(42), urls(42));
+            assert_ne!(urls(42), urls(43));
+        }
+    }
+
+    mod validation_config {
+        use super::*;
+
+        #[test]
+        fn should_fail_fast_only_without_error_budget() {
+            let default = ValidationConfig::default();
+            assert_eq!(
+                default.with_error_budget(0.0).fail_fast_threshold,
+                Some(DEFAULT_FAIL_FAST_THRESHOLD)
+            );
+            assert_eq!(default.with_error_budget(0.01).fail_fast_threshold, None);
+
+            let explicit = ValidationConfig {
+                fail_fast_threshold: Some(3),
+                ..Default::default()
+            };
+            assert_eq!(
+                explicit.with_error_budget(0.01).fail_fast_threshold,
+                Some(3)
+            );
+        }
+    }
+
+    mod connection_pool {
+        use super::*;
+
+        #[tokio::test]
+        async fn should_use_most_recently_used_http1_connection() {
+            let pool = ConnectionPool::<ReqwestEngine>::new(
+                Protocol::Http1,
+                ConnectionMode::KeepAlive,
+                3,
+                3,
+            );
+            let mut indices = Vec::new();
+            for _ in 0..3 {
+                indices.push(pool.acquire().await.index);
+            }
+            assert_eq!(indices, vec![0, 0, 0]);
+
+            let first = pool.acquire().await;
+            let second = pool.acquire().await;
+            assert_eq!((first.index, second.index), (0, 1));
+            drop(second);
+            assert_eq!(pool.acquire().await.index, 1);
+        }
+
+        #[tokio::test]
+        async fn should_rotate_http1_connections_beyond_max_in_flight() {
+            let pool = ConnectionPool::<ReqwestEngine>::new(
+                Protocol::Http1,
+                ConnectionMode::KeepAlive,
+                3,
+                1,
+            );
+            let mut indices = Vec::new();
+            for _ in 0..4 {
+                indices.push(pool.acquire().await.index);
+            }
+            assert_eq!(indices, vec![0, 1, 2, 0]);
+        }
+
+        #[tokio::test]
+        async fn should_lend_http1_connection_to_one_request_at_a_time() {
+            let pool = ConnectionPool::<ReqwestEngine>::new(
+                Protocol::Http1,
+                ConnectionMode::KeepAlive,
+                1,
+                1,
+            );
+            let connection = pool.acquire().await;
+            let second = time::timeout(Duration::from_millis(50), pool.acquire()).await;
+            assert!(second.is_err());
+            drop(connection);
+            let second = time::timeout(Duration::from_millis(50), pool.acquire()).await;
+            assert!(second.is_ok());
+        }
+
+        #[tokio::test]
+        async fn should_share_http2_connections() {
+            let pool = ConnectionPool::<ReqwestEngine>::new(
+                Protocol::H2c,
+                ConnectionMode::KeepAlive,
+                2,
+                8,
+            );
+            let first = pool.acquire().await;
+            let second = pool.acquire().await;
+            let third = pool.acquire().await;
+            assert_eq!((first.index, second.index, third.index), (0, 1, 0));
+        }
+    }
+}
@@ -0,0 +1,687 @@
+use crate::utils::http_load_tester::{
+    ConnectionMode, PreparedHttpRequest, Protocol, CLIENT_TIMEOUT,
+};
+use bytes::{Buf, BytesMut};
+use clap::ValueEnum;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::client::conn::http2;
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, CONNECTION, CONTENT_LENGTH, HOST, TRANSFER_ENCODING,
+};
+use hyper::{Method, Request, StatusCode, Uri};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::io::ErrorKind;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::{task, time};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// reqwest with one client per connection.
+    Reqwest,
+
+    /// HTTP/1.1 requests that are serialized once and written as is, the responses are parsed with httparse.
+    /// h2c uses hyper's low-level connection API. No connection pool, redirects or URL parsing during the load test.
+    Hyper,
+
+    /// Locally installed wrk (HTTP/1.1 only).
+    Wrk,
+
+    /// Locally installed oha.
+    Oha,
+
+    /// Locally installed k6 (HTTP/1.1 only).
+    K6,
+}
+
+impl Engine {
+    /// Whether the load is generated by an external tool instead of the built-in client.
+    pub fn is_external(&self) -> bool {
+        matches!(self, Engine::Wrk | Engine::Oha | Engine::K6)
+    }
+
+    pub fn supports(&self, protocol: Protocol) -> bool {
+        match self {
+            Engine::Wrk | Engine::K6 => protocol == Protocol::Http1,
+            Engine::Reqwest | Engine::Hyper | Engine::Oha => true,
+        }
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Engine::Reqwest => write!(f, "reqwest"),
+            Engine::Hyper => write!(f, "hyper"),
+            Engine::Wrk => write!(f, "wrk"),
+            Engine::Oha => write!(f, "oha"),
+            Engine::K6 => write!(f, "k6"),
+        }
+    }
+}
+
+/// The HTTP client used by the load tester.
+/// An instance is a single connection, which is established with the first request.
+/// HTTP/1.1 connections are only used by one request at a time.
+pub trait LoadEngine: Sized + Send + Sync + 'static {
+    /// Engine-specific form of a request, built once before the load test.
+    type Request: Send + Sync + 'static;
+
+    fn prepare(
+        request: &PreparedHttpRequest,
+        protocol: Protocol,
+        connection_mode: ConnectionMode,
+    ) -> Self::Request;
+
+    fn new(protocol: Protocol, connection_mode: ConnectionMode) -> Self;
+
+    fn send(
+        &self,
+        request: &Self::Request,
+    ) -> impl Future<Output = Result<EngineResponse, RequestError>> + Send;
+}
+
+pub struct EngineResponse {
+    pub status: StatusCode,
+
+    /// When the response headers have arrived.
+    pub headers_received: Instant,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    Timeout,
+    Io(std::io::Error),
+    Reqwest(reqwest::Error),
+    Hyper(hyper::Error),
+}
+
+impl RequestError {
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            RequestError::Timeout => true,
+            RequestError::Reqwest(e) => e.is_timeout(),
+            _ => false,
+        }
+    }
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Timeout => write!(f, "request timed out after {:?}", CLIENT_TIMEOUT),
+            RequestError::Io(e) => write!(f, "{}", e),
+            RequestError::Reqwest(e) => write!(f, "{}", e),
+            RequestError::Hyper(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RequestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RequestError::Timeout => None,
+            RequestError::Io(e) => Some(e),
+            RequestError::Reqwest(e) => Some(e),
+            RequestError::Hyper(e) => Some(e),
+        }
+    }
+}
+
+fn header_map(headers: &[(String, String)]) -> HeaderMap {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                HeaderName::from_bytes(name.as_bytes()).expect("Invalid header name"),
+                HeaderValue::from_str(value).expect("Invalid header value"),
+            )
+        })
+        .collect()
+}
+
+pub struct ReqwestEngine {
+    client: reqwest::Client,
+}
+
+impl LoadEngine for ReqwestEngine {
+    type Request = reqwest::Request;
+
+    fn prepare(
+        request: &PreparedHttpRequest,
+        _protocol: Protocol,
+        _connection_mode: ConnectionMode,
+    ) -> reqwest::Request {
+        let url = reqwest::Url::parse(&request.url).expect("Invalid URL");
+        let mut prepared = reqwest::Request::new(request.method.clone(), url);
+        *prepared.headers_mut() = header_map(&request.headers);
+        if let Some(body) = &request.body {
+            *prepared.body_mut() = Some(body.clone().into());
+        }
+        prepared
+    }
+
+    fn new(protocol: Protocol, connection_mode: ConnectionMode) -> ReqwestEngine {
+        let mut builder = reqwest::Client::builder().timeout(CLIENT_TIMEOUT);
+        if connection_mode == ConnectionMode::Close {
+            // No idle connections are kept, so every request needs a new one.
+            builder = builder
+                .pool_max_idle_per_host(0)
+                .default_headers(HeaderMap::from_iter([(
+                    CONNECTION,
+                    HeaderValue::from_static("close"),
+                )]));
+        }
+        let client = match protocol {
+            Protocol::Http1 => builder.http1_only(),
+            Protocol::H2c => builder.http2_prior_knowledge(),
+        }
+        .build()
+        .unwrap();
+        ReqwestEngine { client }
+    }
+
+    async fn send(&self, request: &reqwest::Request) -> Result<EngineResponse, RequestError> {
+        // The body is never a stream, so the request can always be cloned.
+        let response = self
+            .client
+            .execute(request.try_clone().unwrap())
+            .await
+            .map_err(RequestError::Reqwest)?;
+        let headers_received = Instant::now();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await.map_err(RequestError::Reqwest)?;
+        Ok(EngineResponse {
+            status,
+            headers_received,
+            headers,
+            body,
+        })
+    }
+}
+
+pub struct HyperEngine {
+    connection_mode: ConnectionMode,
+
+    /// The keep-alive connection, `None` until the first request or after it has been closed.
+    connection: Mutex<Option<HyperConnection>>,
+}
+
+pub enum HyperRequest {
+    /// Serialized once, every send writes the same bytes to the connection.
+    Http1 {
+        /// Host and port to connect to.
+        authority: String,
+        bytes: Bytes,
+
+        /// Responses to HEAD requests have no body, whatever their headers say.
+        head: bool,
+    },
+
+    /// hyper's HTTP/2 codec encodes the frames itself, so the request is cloned for every send,
+    /// which only copies the header map and reference-counted handles.
+    H2c {
+        /// Host and port to connect to.
+        authority: String,
+        request: Box<Request<Full<Bytes>>>,
+    },
+}
+
+impl HyperRequest {
+    fn authority(&self) -> &str {
+        match self {
+            HyperRequest::Http1 { authority, .. } | HyperRequest::H2c { authority, .. } => {
+                authority
+            }
+        }
+    }
+}
+
+enum HyperConnection {
+    Http1(Http1Connection),
+    Http2(http2::SendRequest<Full<Bytes>>),
+}
+
+impl HyperConnection {
+    fn is_closed(&self) -> bool {
+        match self {
+            // Closed HTTP/1.1 connections are dropped right away.
+            HyperConnection::Http1(_) => false,
+            HyperConnection::Http2(sender) => sender.is_closed(),
+        }
+    }
+}
+
+/// Maximum number of headers of a response.
+const MAX_RESPONSE_HEADERS: usize = 64;
+
+/// A plain TCP connection that writes pre-serialized HTTP/1.1 requests and parses the responses with httparse.
+struct Http1Connection {
+    stream: TcpStream,
+
+    /// Received bytes that have not been parsed yet.
+    buffer: BytesMut,
+}
+
+impl Http1Connection {
+    /// Returns the response and whether the connection can be reused.
+    async fn send(
+        &mut self,
+        request: &HyperRequest,
+    ) -> Result<(EngineResponse, bool), RequestError> {
+        let HyperRequest::Http1 { bytes, head, .. } = request else {
+            unreachable!("HTTP/1.1 connection for a HTTP/2 request");
+        };
+        self.stream
+            .write_all(bytes)
+            .await
+            .map_err(RequestError::Io)?;
+
+        // Informational responses (1xx) precede the actual response.
+        let (status, headers) = loop {
+            match parse_response_head(&self.buffer)? {
+                Some((status, headers, length)) => {
+                    self.buffer.advance(length);
+                    if !status.is_informational() {
+                        break (status, headers);
+                    }
+                }
+                None => self.read_more().await?,
+            }
+        };
+        let headers_received = Instant::now();
+
+        let chunked = headers
+            .get_all(TRANSFER_ENCODING)
+            .iter()
+            .any(|value| contains_token(value, "chunked"));
+        let content_length = match headers.get(CONTENT_LENGTH) {
+            None => None,
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                    .ok_or_else(|| invalid_response("Invalid Content-Length"))?,
+            ),
+        };
+        let mut keep_alive = !headers
+            .get_all(CONNECTION)
+            .iter()
+            .any(|value| contains_token(value, "close"));
+        let body =
+            if *head || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED {
+                Bytes::new()
+            } else if chunked {
+                self.read_chunked().await?
+            } else if let Some(length) = content_length {
+                self.read_exact(length).await?
+            } else {
+                // Without framing, the body ends with the connection.
+                keep_alive = false;
+                self.read_to_end().await?
+            };
+        let response = EngineResponse {
+            status,
+            headers_received,
+            headers,
+            body,
+        };
+        Ok((response, keep_alive))
+    }
+
+    async fn read_more(&mut self) -> Result<(), RequestError> {
+        match self.stream.read_buf(&mut self.buffer).await {
+            Ok(0) => Err(RequestError::Io(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "connection closed before the response was complete",
+            ))),
+            Ok(_) => Ok(()),
+            Err(e) => Err(RequestError::Io(e)),
+        }
+    }
+
+    async fn read_exact(&mut self, length: usize) -> Result<Bytes, RequestError> {
+        while self.buffer.len() < length {
+            self.read_more().await?;
+        }
+        Ok(self.buffer.split_to(length).freeze())
+    }
+
+    async fn read_to_end(&mut self) -> Result<Bytes, RequestError> {
+        loop {
+            match self.stream.read_buf(&mut self.buffer).await {
+                Ok(0) => return Ok(self.buffer.split().freeze()),
+                Ok(_) => {}
+                Err(e) => return Err(RequestError::Io(e)),
+            }
+        }
+    }
+
+    async fn read_chunked(&mut self) -> Result<Bytes, RequestError> {
+        let mut body = BytesMut::new();
+        loop {
+            let (line_length, size) = match httparse::parse_chunk_size(&self.buffer) {
+                Ok(httparse::Status::Complete(chunk)) => chunk,
+                Ok(httparse::Status::Partial) => {
+                    self.read_more().await?;
+                    continue;
+                }
+                Err(_) => return Err(invalid_response("Invalid chunk size")),
+            };
+            self.buffer.advance(line_length);
+            if size == 0 {
+                break;
+            }
+            // The data is followed by a CRLF.
+            let chunk = self.read_exact(size as usize + 2).await?;
+            body.extend_from_slice(&chunk[..size as usize]);
+        }
+
+        // The trailers end with an empty line.
+        loop {
+            match self.buffer.windows(2).position(|window| window == b"\r\n") {
+                Some(0) => {
+                    self.buffer.advance(2);
+                    return Ok(body.freeze());
+                }
+                Some(end) => self.buffer.advance(end + 2),
+                None => self.read_more().await?,
+            }
+        }
+    }
+}
+
+/// Returns the status, the headers and the length of the head, or `None` if the head is incomplete.
+fn parse_response_head(
+    buffer: &[u8],
+) -> Result<Option<(StatusCode, HeaderMap, usize)>, RequestError> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_RESPONSE_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+    let length = match response.parse(buffer) {
+        Ok(httparse::Status::Complete(length)) => length,
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(e) => return Err(invalid_response(&format!("Invalid response: {e}"))),
+    };
+    let status = StatusCode::from_u16(response.code.unwrap())
+        .map_err(|_| invalid_response("Invalid status code"))?;
+    let mut header_map = HeaderMap::with_capacity(response.headers.len());
+    for header in response.headers.iter() {
+        header_map.append(
+            HeaderName::from_bytes(header.name.as_bytes())
+                .map_err(|_| invalid_response("Invalid header name"))?,
+            HeaderValue::from_bytes(header.value)
+                .map_err(|_| invalid_response("Invalid header value"))?,
+        );
+    }
+    Ok(Some((status, header_map, length)))
+}
+
+/// Whether a comma-separated header value contains the token, e.g. "close" in "Connection: close".
+fn contains_token(value: &HeaderValue, token: &str) -> bool {
+    value
+        .as_bytes()
+        .split(|byte| *byte == b',')
+        .any(|part| part.trim_ascii().eq_ignore_ascii_case(token.as_bytes()))
+}
+
+fn invalid_response(message: &str) -> RequestError {
+    RequestError::Io(io::Error::new(ErrorKind::InvalidData, message.to_string()))
+}
+
+async fn send_http2(
+    mut sender: http2::SendRequest<Full<Bytes>>,
+    request: &HyperRequest,
+) -> Result<EngineResponse, RequestError> {
+    let HyperRequest::H2c { request, .. } = request else {
+        unreachable!("HTTP/2 connection for a HTTP/1.1 request");
+    };
+    sender.ready().await.map_err(RequestError::Hyper)?;
+    let response = sender
+        .send_request(request.as_ref().clone())
+        .await
+        .map_err(RequestError::Hyper)?;
+    let headers_received = Instant::now();
+    let (parts, body) = response.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(RequestError::Hyper)?
+        .to_bytes();
+    Ok(EngineResponse {
+        status: parts.status,
+        headers_received,
+        headers: parts.headers,
+        body,
+    })
+}
+
+impl HyperEngine {
+    async fn connect(request: &HyperRequest) -> Result<HyperConnection, RequestError> {
+        let stream = TcpStream::connect(request.authority())
+            .await
+            .map_err(RequestError::Io)?;
+        stream.set_nodelay(true).map_err(RequestError::Io)?;
+        match request {
+            HyperRequest::Http1 { .. } => Ok(HyperConnection::Http1(Http1Connection {
+                stream,
+                buffer: BytesMut::new(),
+            })),
+            HyperRequest::H2c { .. } => {
+                let (sender, connection) =
+                    http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                        .await
+                        .map_err(RequestError::Hyper)?;
+                task::spawn(connection);
+                Ok(HyperConnection::Http2(sender))
+            }
+        }
+    }
+
+    async fn send_request(&self, request: &HyperRequest) -> Result<EngineResponse, RequestError> {
+        if self.connection_mode == ConnectionMode::Close {
+            return match Self::connect(request).await? {
+                HyperConnection::Http1(mut connection) => {
+                    connection.send(request).await.map(|(response, _)| response)
+                }
+                HyperConnection::Http2(sender) => send_http2(sender, request).await,
+            };
+        }
+
+        let mut connection = self.connection.lock().await;
+        if connection.as_ref().is_none_or(HyperConnection::is_closed) {
+            *connection = Some(Self::connect(request).await?);
+        }
+        match connection.as_mut().unwrap() {
+            // HTTP/2 connections are shared by concurrent streams, so the lock is released before sending.
+            HyperConnection::Http2(sender) => {
+                let sender = sender.clone();
+                drop(connection);
+                send_http2(sender, request).await
+            }
+            HyperConnection::Http1(http1_connection) => {
+                let result = http1_connection.send(request).await;
+                // After an error, the connection is in an unknown state.
+                if !matches!(result, Ok((_, true))) {
+                    *connection = None;
+                }
+                result.map(|(response, _)| response)
+            }
+        }
+    }
+}
+
+impl LoadEngine for HyperEngine {
+    type Request = HyperRequest;
+
+    fn prepare(
+        request: &PreparedHttpRequest,
+        protocol: Protocol,
+        connection_mode: ConnectionMode,
+    ) -> HyperRequest {
+        let uri: Uri = request.url.parse().expect("Invalid URL");
+        let authority = uri.authority().expect("URL without host").to_string();
+        let body = request.body.clone().map(Bytes::from).unwrap_or_default();
+
+        // The headers of the request replace the default ones.
+        let mut headers = HeaderMap::new();
+        if connection_mode == ConnectionMode::Close {
+            headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        }
+
+        match protocol {
+            // HTTP/1.1 sends the origin-form URI and the authority as Host header.
+            Protocol::Http1 => {
+                headers.insert(
+                    HOST,
+                    HeaderValue::from_str(&authority).expect("Invalid host"),
+                );
+                if request.body.is_some() {
+                    headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+                }
+                headers.extend(header_map(&request.headers));
+
+                let mut bytes = BytesMut::new();
+                let path = uri.path_and_query().expect("URL without path");
+                bytes.extend_from_slice(
+                    format!("{} {} HTTP/1.1\r\n", request.method, path).as_bytes(),
+                );
+                for (name, value) in &headers {
+                    bytes.extend_from_slice(name.as_str().as_bytes());
+                    bytes.extend_from_slice(b": ");
+                    bytes.extend_from_slice(value.as_bytes());
+                    bytes.extend_from_slice(b"\r\n");
+                }
+                bytes.extend_from_slice(b"\r\n");
+                bytes.extend_from_slice(&body);
+                HyperRequest::Http1 {
+                    authority,
+                    bytes: bytes.freeze(),
+                    head: request.method == Method::HEAD,
+                }
+            }
+            // HTTP/2 sends the absolute URI, the authority becomes a pseudo-header.
+            Protocol::H2c => {
+                headers.extend(header_map(&request.headers));
+                let mut built = Request::builder()
+                    .uri(uri)
+                    .method(request.method.clone())
+                    .body(Full::new(body))
+                    .unwrap();
+                *built.headers_mut() = headers;
+                HyperRequest::H2c {
+                    authority,
+                    request: Box::new(built),
+                }
+            }
+        }
+    }
+
+    fn new(_protocol: Protocol, connection_mode: ConnectionMode) -> HyperEngine {
+        HyperEngine {
+            connection_mode,
+            connection: Mutex::new(None),
+        }
+    }
+
+    async fn send(&self, request: &HyperRequest) -> Result<EngineResponse, RequestError> {
+        match time::timeout(CLIENT_TIMEOUT, self.send_request(request)).await {
+            Ok(response) => response,
+            Err(_) => {
+                // The connection might still be busy with the timed out request.
+                *self.connection.lock().await = None;
+                Err(RequestError::Timeout)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod hyper_engine {
+        use super::*;
+        use tokio::net::TcpListener;
+
+        fn request(url: &str) -> PreparedHttpRequest {
+            PreparedHttpRequest {
+                route: "element".to_string(),
+                url: url.to_string(),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn should_serialize_http1_request_once() {
+            let request = request("http://localhost:3000/api/v1/periodic-table/element?symbol=He");
+            let HyperRequest::Http1 {
+                authority, bytes, ..
+            } = HyperEngine::prepare(&request, Protocol::Http1, ConnectionMode::Close)
+            else {
+                panic!("Expected a HTTP/1.1 request");
+            };
+            assert_eq!(authority, "localhost:3000");
+            assert_eq!(
+                bytes,
+                "GET /api/v1/periodic-table/element?symbol=He HTTP/1.1\r\n\
+                 connection: close\r\n\
+                 host: localhost:3000\r\n\r\n"
+            );
+        }
+
+        #[test]
+        fn should_send_absolute_uri_with_h2c() {
+            let request = request("http://localhost:3000/api/v1/periodic-table/element?symbol=He");
+            let HyperRequest::H2c { request, .. } =
+                HyperEngine::prepare(&request, Protocol::H2c, ConnectionMode::KeepAlive)
+            else {
+                panic!("Expected a HTTP/2 request");
+            };
+            assert_eq!(request.uri().scheme_str(), Some("http"));
+            assert!(!request.headers().contains_key(HOST));
+        }
+
+        #[tokio::test]
+        async fn should_parse_http1_responses_on_one_connection() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let server = task::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 1024];
+                for response in [
+                    "HTTP/1.1 200 OK\r\ncontent-length: 7\r\n\r\n{\"a\":1}",
+                    "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n3\r\n{\"b\r\n4\r\n\":2}\r\n0\r\n\r\n",
+                ] {
+                    let _ = stream.read(&mut buffer).await.unwrap();
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+
+            let engine = HyperEngine::new(Protocol::Http1, ConnectionMode::KeepAlive);
+            let request = HyperEngine::prepare(
+                &request(&format!("http://{address}/")),
+                Protocol::Http1,
+                ConnectionMode::KeepAlive,
+            );
+            let first = engine.send(&request).await.unwrap();
+            assert_eq!(first.status, StatusCode::OK);
+            assert_eq!(first.body, "{\"a\":1}");
+            let second = engine.send(&request).await.unwrap();
+            assert_eq!(second.body, "{\"b\":2}");
+            server.await.unwrap();
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use crate::utils::http_load_tester::{ConnectionMode, LoadMode, Protocol, ValidationConfig};
+use crate::utils::upstream_latency::LatencyProfile;
 use indexmap::IndexMap;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use std::fs;
 
 #[derive(Serialize, Deserialize)]
@@ -50,8 +52,53 @@ pub struct WebBenchmarkMetaData {
     #[serde(default = "default_as_false")]
     pub extended_warmup: bool,
 
+    /// Maximum number of requests in flight.
+    #[serde(alias = "max_in_flight")]
     pub concurrency: Option<usize>,
 
+    pub load_mode: Option<LoadMode>,
+
+    pub target_rps: Option<usize>,
+
+    /// Concurrency levels used by `--sweep`, in ascending order.
+    #[serde(default, deserialize_with = "deserialize_sweep_concurrency")]
+    pub sweep_concurrency: Option<Vec<usize>>,
+
+    /// p99 latency SLO in milliseconds used by `--sweep`.
+    pub latency_slo: Option<u64>,
+
+    #[serde(default)]
+    pub validation: ValidationConfig,
+
+    /// Fraction of requests that may fail, e.g. 0.01 for 1%.
+    pub error_budget: Option<f64>,
+
+    /// Relative weight per route, e.g. `{ element: 90, shells: 10 }`.
+    /// By default, all routes are requested equally often.
+    pub route_weights: Option<IndexMap<String, u32>>,
+
+    /// Seconds to linearly increase the number of active connections before measuring.
+    pub ramp_up: Option<u64>,
+
+    /// Seconds after the ramp-up that are not measured.
+    pub exclude_start: Option<u64>,
+
+    /// Seconds after the measurement window until the load test stops.
+    pub exclude_end: Option<u64>,
+
+    pub protocol: Option<Protocol>,
+
+    /// Number of connections, independent of the number of requests in flight.
+    pub connections: Option<usize>,
+
+    /// Concurrent streams per HTTP/2 connection (h2c only).
+    pub streams_per_connection: Option<usize>,
+
+    pub connection_mode: Option<ConnectionMode>,
+
+    /// Latency profile of the web data source.
+    pub upstream_latency: Option<LatencyProfile>,
+
     pub copy: Option<Vec<CopyValue>>,
 }
 
@@ -71,6 +118,32 @@ fn default_as_false() -> bool {
     false
 }
 
+fn deserialize_sweep_concurrency<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<usize>>, D::Error> {
+    let levels: Option<Vec<usize>> = Option::deserialize(deserializer)?;
+    if let Some(levels) = &levels {
+        validate_sweep_concurrency(levels).map_err(de::Error::custom)?;
+    }
+    Ok(levels)
+}
+
+fn validate_sweep_concurrency(levels: &[usize]) -> Result<(), String> {
+    if levels.is_empty() {
+        return Err("sweep_concurrency must contain at least one level".to_string());
+    }
+    if levels[0] == 0 {
+        return Err("sweep_concurrency must not contain 0".to_string());
+    }
+    if levels.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(format!(
+            "sweep_concurrency must be in ascending order: {:?}",
+            levels
+        ));
+    }
+    Ok(())
+}
+
 impl BenchmarkMetaData {
     pub fn print_info(&self) {
         println!(" - Language: {}", self.language);
@@ -109,6 +182,24 @@ impl WebBenchmarkMetaData {
             self.framework_version_regex.debug_serialize()
         );
         println!(" - Concurrency: {:?}", self.concurrency);
+        println!(" - Load mode: {:?}", self.load_mode);
+        println!(" - Target RPS: {:?}", self.target_rps);
+        println!(" - Sweep concurrency: {:?}", self.sweep_concurrency);
+        println!(" - Latency SLO: {:?}", self.latency_slo);
+        println!(" - Validation: {:?}", self.validation);
+        println!(" - Error budget: {:?}", self.error_budget);
+        println!(" - Route weights: {:?}", self.route_weights);
+        println!(" - Ramp-up: {:?}", self.ramp_up);
+        println!(" - Exclude start: {:?}", self.exclude_start);
+        println!(" - Exclude end: {:?}", self.exclude_end);
+        println!(" - Protocol: {:?}", self.protocol);
+        println!(" - Connections: {:?}", self.connections);
+        println!(
+            " - Streams per connection: {:?}",
+            self.streams_per_connection
+        );
+        println!(" - Connection mode: {:?}", self.connection_mode);
+        println!(" - Upstream latency: {:?}", self.upstream_latency);
         println!(" - Copy: {:?}", self.copy);
         println!();
     }
@@ -132,3 +223,21 @@ impl VersionRegexSerializer for Option<IndexMap<String, String>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_accept_ascending_sweep_concurrency() {
+        assert!(validate_sweep_concurrency(&[1, 4, 16]).is_ok());
+    }
+
+    #[test]
+    fn should_reject_invalid_sweep_concurrency() {
+        assert!(validate_sweep_concurrency(&[]).is_err());
+        assert!(validate_sweep_concurrency(&[0, 4]).is_err());
+        assert!(validate_sweep_concurrency(&[16, 4]).is_err());
+        assert!(validate_sweep_concurrency(&[4, 4]).is_err());
+    }
+}
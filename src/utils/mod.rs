@@ -1,12 +1,18 @@
 pub mod copy_files;
+pub mod cpu_affinity;
+pub mod data_rotation;
 pub mod docker_runner;
 pub mod docker_stats;
+pub mod expectation;
+pub mod external_load_tester;
 pub mod http_load_tester;
+pub mod load_engine;
 pub mod meta_data_parser;
 pub mod panic;
 pub mod percentile;
+pub mod request_trace;
 pub mod result_reader;
 pub mod result_writer;
-pub mod serialization;
+pub mod upstream_latency;
 pub mod version;
 pub mod version_migrator;
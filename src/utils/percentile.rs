@@ -1,24 +1,34 @@
+use crate::utils::http_load_tester::CLIENT_TIMEOUT;
 use crate::utils::panic::panic_with_stacktrace;
+use serde::{Deserialize, Serialize};
 
 /// Returns the 1st percentile of the given values.
 /// Avoids MIN if possible.
-pub fn p1<T: Copy>(values: &Vec<T>) -> T {
+pub fn p1<T: Copy>(values: &[T]) -> T {
     p_higher(values, 0.01)
 }
 
 /// Returns the 50th percentile of the given values.
 /// Avoids MAX if possible.
-pub fn p50<T: Copy>(values: &Vec<T>) -> T {
+pub fn p50<T: Copy>(values: &[T]) -> T {
     p_lower(values, 0.5)
 }
 
 /// Returns the 99th percentile of the given values.
 /// Avoids MAX if possible.
-pub fn p99<T: Copy>(values: &Vec<T>) -> T {
+pub fn p99<T: Copy>(values: &[T]) -> T {
     p_lower(values, 0.99)
 }
 
-fn p_lower<T: Copy>(values: &Vec<T>, percentile: f64) -> T {
+/// Returns the 99.9th percentile of the given values.
+/// Avoids MAX if possible.
+/// Latencies are recorded in a [Histogram], so this is only used to check its rank semantics.
+#[cfg(test)]
+pub fn p999<T: Copy>(values: &[T]) -> T {
+    p_lower(values, 0.999)
+}
+
+fn p_lower<T: Copy>(values: &[T], percentile: f64) -> T {
     if values.is_empty() {
         panic_with_stacktrace("Cannot calculate percentile of empty vector");
     }
@@ -30,7 +40,7 @@ fn p_lower<T: Copy>(values: &Vec<T>, percentile: f64) -> T {
     values[index]
 }
 
-fn p_higher<T: Copy>(values: &Vec<T>, percentile: f64) -> T {
+fn p_higher<T: Copy>(values: &[T], percentile: f64) -> T {
     if values.is_empty() {
         panic_with_stacktrace("Cannot calculate percentile of empty vector");
     }
@@ -48,6 +58,171 @@ fn p_higher<T: Copy>(values: &Vec<T>, percentile: f64) -> T {
     values[index]
 }
 
+/// Number of bits used for the linear sub-buckets of each power of two.
+/// 8 bits result in a relative error of less than 1%.
+const SUB_BUCKET_BITS: u32 = 8;
+const SUB_BUCKET_COUNT: usize = 1 << SUB_BUCKET_BITS;
+const SUB_BUCKET_HALF_COUNT: usize = SUB_BUCKET_COUNT / 2;
+
+/// Latencies in microseconds cannot be longer than the client timeout.
+const HIGHEST_TRACKABLE_VALUE: u64 = CLIENT_TIMEOUT.as_micros() as u64;
+const BUCKET_COUNT: usize = bucket_index(HIGHEST_TRACKABLE_VALUE) + 1;
+
+/// A fixed-memory histogram with logarithmic buckets (similar to HdrHistogram).
+/// Values below 256 are recorded exactly, larger values with a relative error of less than 1%.
+/// Values above [HIGHEST_TRACKABLE_VALUE] are recorded as [HIGHEST_TRACKABLE_VALUE].
+/// Histograms can be merged without losing precision.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "SparseHistogram", try_from = "SparseHistogram")]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total_count: u64,
+    max: u64,
+}
+
+/// Serialized form of [Histogram] which only contains non-empty buckets.
+#[derive(Serialize, Deserialize)]
+struct SparseHistogram {
+    max: u64,
+
+    /// Pairs of (bucket index, count)
+    buckets: Vec<(usize, u64)>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; BUCKET_COUNT],
+            total_count: 0,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let value = value.min(HIGHEST_TRACKABLE_VALUE);
+        self.counts[bucket_index(value)] += 1;
+        self.total_count += 1;
+        self.max = self.max.max(value);
+    }
+
+    /// Adds all values of `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        self.total_count += other.total_count;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_count == 0
+    }
+
+    /// Number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.total_count
+    }
+
+    /// Returns the given percentile with the same rank semantics as [p50] and [p99].
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.is_empty() {
+            panic_with_stacktrace("Cannot calculate percentile of empty histogram");
+        }
+
+        let rank = ((self.total_count as f64 * percentile) as u64).max(1);
+        let mut cumulative = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank {
+                return bucket_highest_value(index).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn p50(&self) -> u64 {
+        self.percentile(0.5)
+    }
+
+    pub fn p90(&self) -> u64 {
+        self.percentile(0.9)
+    }
+
+    pub fn p99(&self) -> u64 {
+        self.percentile(0.99)
+    }
+
+    pub fn p999(&self) -> u64 {
+        self.percentile(0.999)
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+}
+
+impl From<Histogram> for SparseHistogram {
+    fn from(histogram: Histogram) -> Self {
+        SparseHistogram {
+            max: histogram.max,
+            buckets: histogram
+                .counts
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(index, count)| (index, *count))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<SparseHistogram> for Histogram {
+    type Error = String;
+
+    fn try_from(sparse: SparseHistogram) -> Result<Self, Self::Error> {
+        let mut histogram = Histogram::new();
+        for (index, count) in sparse.buckets {
+            let bucket = histogram
+                .counts
+                .get_mut(index)
+                .ok_or_else(|| format!("Bucket {index} is out of range (0..{BUCKET_COUNT})"))?;
+            *bucket += count;
+            histogram.total_count += count;
+        }
+        histogram.max = sparse.max;
+        Ok(histogram)
+    }
+}
+
+const fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKET_COUNT as u64 {
+        return value as usize;
+    }
+
+    // Keep the highest SUB_BUCKET_BITS bits of the value.
+    let magnitude = u64::BITS - 1 - value.leading_zeros();
+    let shift = magnitude + 1 - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) as usize - SUB_BUCKET_HALF_COUNT;
+    SUB_BUCKET_COUNT + (shift as usize - 1) * SUB_BUCKET_HALF_COUNT + sub_bucket
+}
+
+fn bucket_highest_value(index: usize) -> u64 {
+    if index < SUB_BUCKET_COUNT {
+        return index as u64;
+    }
+
+    let shift = ((index - SUB_BUCKET_COUNT) / SUB_BUCKET_HALF_COUNT + 1) as u32;
+    let sub_bucket =
+        ((index - SUB_BUCKET_COUNT) % SUB_BUCKET_HALF_COUNT + SUB_BUCKET_HALF_COUNT) as u64;
+    ((sub_bucket + 1) << shift) - 1
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,4 +324,123 @@ mod tests {
             assert_eq!(p99(&values), 990);
         }
     }
+
+    mod test_p999 {
+        use super::*;
+
+        #[test]
+        fn test_p999_of_10_values() {
+            let values: Vec<i32> = n(10);
+            assert_eq!(p999(&values), 9);
+        }
+
+        #[test]
+        fn test_p999_of_1000_values() {
+            let values: Vec<i32> = n(1000);
+            assert_eq!(p999(&values), 999);
+        }
+
+        #[test]
+        fn test_p999_of_10000_values() {
+            let values: Vec<i32> = n(10000);
+            assert_eq!(p999(&values), 9990);
+        }
+    }
+
+    mod test_histogram {
+        use super::*;
+
+        fn histogram(values: impl Iterator<Item = u64>) -> Histogram {
+            let mut histogram = Histogram::new();
+            for value in values {
+                histogram.record(value);
+            }
+            histogram
+        }
+
+        #[test]
+        fn should_match_exact_percentiles_for_small_values() {
+            let histogram = histogram(1..=100);
+            assert_eq!(histogram.p50(), 50);
+            assert_eq!(histogram.p90(), 90);
+            assert_eq!(histogram.p99(), 99);
+            assert_eq!(histogram.max(), 100);
+        }
+
+        #[test]
+        fn should_match_percentiles_of_sorted_values() {
+            let values: Vec<u64> = (1..=200).collect();
+            let histogram = histogram(values.iter().copied());
+            assert_eq!(histogram.p50(), p50(&values));
+            assert_eq!(histogram.p99(), p99(&values));
+            assert_eq!(histogram.p999(), p999(&values));
+        }
+
+        #[test]
+        fn should_stay_within_relative_error() {
+            let histogram = histogram(1..=1_000_000);
+            for (actual, expected) in [
+                (histogram.p50(), 500_000),
+                (histogram.p90(), 900_000),
+                (histogram.p99(), 990_000),
+                (histogram.p999(), 999_000),
+            ] {
+                let error = (actual as f64 - expected as f64).abs() / expected as f64;
+                assert!(error < 0.01, "{actual} is not close to {expected}");
+            }
+            assert_eq!(histogram.max(), 1_000_000);
+        }
+
+        #[test]
+        fn should_map_every_bucket_to_its_highest_value() {
+            for value in [
+                0,
+                1,
+                255,
+                256,
+                257,
+                511,
+                512,
+                1_000_000,
+                HIGHEST_TRACKABLE_VALUE,
+            ] {
+                let index = bucket_index(value);
+                assert!(bucket_highest_value(index) >= value);
+                assert_eq!(bucket_index(bucket_highest_value(index)), index);
+            }
+            assert_eq!(bucket_index(HIGHEST_TRACKABLE_VALUE), BUCKET_COUNT - 1);
+        }
+
+        #[test]
+        fn should_clamp_values_above_the_client_timeout() {
+            let histogram = histogram([100, u64::MAX, u64::MAX].into_iter());
+            assert_eq!(histogram.p50(), 100);
+            assert_eq!(histogram.p99(), HIGHEST_TRACKABLE_VALUE);
+            assert_eq!(histogram.max(), HIGHEST_TRACKABLE_VALUE);
+        }
+
+        #[test]
+        fn should_merge_losslessly() {
+            let mut merged = histogram(1..=500);
+            merged.merge(&histogram(501..=100_000));
+            assert_eq!(merged, histogram(1..=100_000));
+        }
+
+        #[test]
+        fn should_serialize_and_deserialize() {
+            let histogram = histogram([3, 3, 700, 12_345].into_iter());
+            let json = serde_json::to_string(&histogram).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Histogram>(json.as_str()).unwrap(),
+                histogram
+            );
+        }
+
+        #[test]
+        fn should_reject_out_of_range_bucket() {
+            let json = format!(r#"{{"max": 3, "buckets": [[3, 1], [{BUCKET_COUNT}, 1]]}}"#);
+            let error = serde_json::from_str::<Histogram>(&json).unwrap_err();
+            assert!(error.to_string().contains("out of range"), "{error}");
+        }
+    }
 }
@@ -0,0 +1,319 @@
+use crate::utils::expectation::{Expectation, HeaderExpectation};
+use crate::utils::http_load_tester::PreparedHttpRequest;
+use indexmap::IndexMap;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Fraction of successful responses whose body is written to a recorded trace.
+/// The bodies of failed responses are always written.
+pub const RECORDED_BODY_RATE: f64 = 0.01;
+
+/// One request of a recorded trace, stored as one JSON object per line.
+///
+/// Example:
+/// `{"timestamp_ms": 12, "method": "GET", "path": "/api/v1/periodic-table/element?symbol=He", "expected_response": {"name": "Helium"}}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Send time in milliseconds, relative to any fixed point like the start of the recording.
+    pub timestamp_ms: u64,
+
+    #[serde(default = "default_method")]
+    pub method: String,
+
+    /// Path and query, relative to the benchmarked server.
+    pub path: String,
+
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub headers: IndexMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+
+    /// Label used to group the results. Defaults to the path without query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+
+    #[serde(default = "default_status")]
+    pub expected_status: u16,
+
+    /// Expected JSON response, see `Expectation`. The response is not validated if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_response: Option<Expectation>,
+
+    /// Required and forbidden response headers, see `HeaderExpectation`.
+    #[serde(default, skip_serializing_if = "HeaderExpectation::is_empty")]
+    pub expected_headers: HeaderExpectation,
+}
+
+impl TraceEntry {
+    fn from_request(request: &PreparedHttpRequest, sent_at: Duration) -> Self {
+        TraceEntry {
+            timestamp_ms: sent_at.as_millis() as u64,
+            method: request.method.to_string(),
+            path: path_of(&request.url).to_string(),
+            headers: request.headers.iter().cloned().collect(),
+            body: request.body.clone(),
+            route: Some(request.route.clone()),
+            expected_status: request.expected_status.as_u16(),
+            expected_response: request.expected_response.clone(),
+            expected_headers: request.expected_headers.clone(),
+        }
+    }
+}
+
+/// Path and query of an absolute URL.
+fn path_of(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    without_scheme
+        .find('/')
+        .map_or("/", |index| &without_scheme[index..])
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// A trace ready to be replayed.
+pub struct Replay {
+    pub requests: Vec<PreparedHttpRequest>,
+
+    /// Send time of each request relative to the start, already scaled by the speed.
+    pub schedule: Vec<Duration>,
+}
+
+/// Reads a JSONL trace. Empty lines are skipped.
+pub fn read_trace(path: &str) -> Vec<TraceEntry> {
+    let contents =
+        fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read trace {}: {}", path, e));
+    parse_trace(&contents).unwrap_or_else(|e| panic!("Invalid trace {}: {}", path, e))
+}
+
+fn parse_trace(contents: &str) -> Result<Vec<TraceEntry>, String> {
+    let mut entries = Vec::new();
+    for (line_index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: TraceEntry =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", line_index + 1, e))?;
+        entries.push(entry);
+    }
+    if entries.is_empty() {
+        return Err("no requests".to_string());
+    }
+    entries.sort_by_key(|entry| entry.timestamp_ms);
+    Ok(entries)
+}
+
+/// Turns the trace into requests to `base_url`.
+/// A `speed` of 2.0 replays the trace twice as fast as recorded.
+pub fn prepare_replay(entries: &[TraceEntry], base_url: &str, speed: f64) -> Replay {
+    if !(speed.is_finite() && speed > 0.0) {
+        panic!(
+            "The replay speed must be a positive finite number, got {}",
+            speed
+        );
+    }
+
+    let first = entries[0].timestamp_ms;
+    let requests = entries
+        .iter()
+        .map(|entry| PreparedHttpRequest {
+            route: entry
+                .route
+                .clone()
+                .unwrap_or_else(|| entry.path.split('?').next().unwrap().to_string()),
+            method: Method::from_bytes(entry.method.as_bytes())
+                .unwrap_or_else(|_| panic!("Invalid method in trace: {}", entry.method)),
+            url: format!("{}{}", base_url, entry.path),
+            headers: entry
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            body: entry.body.clone(),
+            expected_status: StatusCode::from_u16(entry.expected_status)
+                .unwrap_or_else(|_| panic!("Invalid status in trace: {}", entry.expected_status)),
+            expected_response: entry.expected_response.clone(),
+            rotated_response: None,
+            expected_headers: entry.expected_headers.clone(),
+        })
+        .collect();
+    let schedule = entries
+        .iter()
+        .map(|entry| Duration::from_millis(entry.timestamp_ms - first).div_f64(speed))
+        .collect();
+    Replay { requests, schedule }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    #[default]
+    Success,
+
+    /// The response did not pass the validation.
+    Invalid,
+
+    /// The response had an unexpected status.
+    Status,
+
+    /// No response was received.
+    Error,
+}
+
+/// What happened to a sent request, as written to a recorded trace.
+#[derive(Default, Serialize)]
+pub struct RecordedResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_us: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttfb_us: Option<u64>,
+    pub outcome: Outcome,
+
+    /// Validation or request error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Body of the response, only for failed and sampled responses.
+    #[serde(rename = "response", skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+/// A line of a recorded trace. It is a valid `TraceEntry`, so the trace can be replayed.
+#[derive(Serialize)]
+struct RecordedEntry {
+    #[serde(flatten)]
+    request: TraceEntry,
+
+    #[serde(flatten)]
+    response: RecordedResponse,
+}
+
+/// Writes every sent request of a load test to a JSONL file. Shared by all tasks.
+pub struct TraceRecorder {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl TraceRecorder {
+    pub fn create(path: &str) -> Self {
+        if let Some(parent_dir) = Path::new(path).parent() {
+            fs::create_dir_all(parent_dir).expect("Failed to create trace directory");
+        }
+        let file =
+            File::create(path).unwrap_or_else(|e| panic!("Failed to create trace {}: {}", path, e));
+        TraceRecorder {
+            writer: Mutex::new(BufWriter::new(file)),
+        }
+    }
+
+    /// `sent_at` is the send time relative to the start of the load test.
+    pub fn record(
+        &self,
+        request: &PreparedHttpRequest,
+        sent_at: Duration,
+        response: RecordedResponse,
+    ) {
+        let line = serde_json::to_string(&RecordedEntry {
+            request: TraceEntry::from_request(request, sent_at),
+            response,
+        })
+        .expect("Failed to serialize trace entry");
+        writeln!(self.writer.lock().unwrap(), "{}", line).expect("Failed to write trace");
+    }
+
+    pub fn flush(&self) {
+        self.writer
+            .lock()
+            .unwrap()
+            .flush()
+            .expect("Failed to write trace");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_trace_with_defaults() {
+        let trace = r#"
+{"timestamp_ms": 1500, "path": "/api/v1/periodic-table/shells?symbol=He", "expected_response": {"shells": [2]}}
+{"timestamp_ms": 1000, "method": "POST", "path": "/echo", "headers": {"Content-Type": "application/json"}, "body": "{}", "expected_status": 201}
+"#;
+        let entries = parse_trace(trace).unwrap();
+        assert_eq!(entries[0].method, "POST");
+        assert_eq!(entries[0].expected_status, 201);
+        assert_eq!(entries[1].method, "GET");
+        assert_eq!(entries[1].expected_status, 200);
+    }
+
+    #[test]
+    fn should_report_invalid_line() {
+        let trace = "{\"timestamp_ms\": 0, \"path\": \"/\"}\n{\"path\": \"/\"}";
+        assert!(parse_trace(trace).unwrap_err().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn should_scale_schedule_by_speed() {
+        let entries =
+            parse_trace("{\"timestamp_ms\": 1000, \"path\": \"/a?x=1\"}\n{\"timestamp_ms\": 3000, \"path\": \"/b\"}")
+                .unwrap();
+        let replay = prepare_replay(&entries, "http://localhost:3000", 2.0);
+        assert_eq!(
+            replay.schedule,
+            vec![Duration::ZERO, Duration::from_secs(1)]
+        );
+        assert_eq!(replay.requests[0].route, "/a");
+        assert_eq!(replay.requests[0].url, "http://localhost:3000/a?x=1");
+    }
+
+    #[test]
+    #[should_panic(expected = "The replay speed must be a positive finite number")]
+    fn should_reject_nan_speed() {
+        // --replay-speed NaN is parsed as f64::NAN, which is neither <= 0 nor > 0.
+        let entries = parse_trace("{\"timestamp_ms\": 0, \"path\": \"/a\"}").unwrap();
+        prepare_replay(&entries, "http://localhost:3000", "NaN".parse().unwrap());
+    }
+
+    #[test]
+    fn should_replay_recorded_entry() {
+        let request = PreparedHttpRequest {
+            route: "element".to_string(),
+            url: "http://localhost:3000/api/v1/periodic-table/element?symbol=He".to_string(),
+            ..Default::default()
+        };
+        let line = serde_json::to_string(&RecordedEntry {
+            request: TraceEntry::from_request(&request, Duration::from_millis(1500)),
+            response: RecordedResponse {
+                status: Some(500),
+                outcome: Outcome::Status,
+                body: Some("oops".to_string()),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+        assert!(line.contains("\"outcome\":\"status\""));
+        assert!(line.contains("\"response\":\"oops\""));
+
+        let entries = parse_trace(&line).unwrap();
+        assert_eq!(entries[0].timestamp_ms, 1500);
+        assert_eq!(entries[0].path, "/api/v1/periodic-table/element?symbol=He");
+        assert_eq!(entries[0].route.as_deref(), Some("element"));
+        assert_eq!(entries[0].expected_status, 200);
+    }
+}
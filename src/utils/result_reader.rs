@@ -1,6 +1,17 @@
+use crate::benchmark::web::LEGACY_COLUMN_DEFAULTS;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 
+/// Columns of web_result.csv that tell runs of the same versions apart.
+const WEB_CONFIGURATION_COLUMNS: &[&str] = &[
+    "load_mode",
+    "protocol",
+    "connection_mode",
+    "upstream_latency",
+    "engine",
+];
+
+#[derive(Default)]
 pub struct ResultMap {
     /// Map of language to a set of directories that have been benchmarked
     /// Language -> Directory (Variant) -> ExistingResult
@@ -23,16 +34,33 @@ pub struct ExistingResult {
     /// List of available versions for the variant.
     pub language_versions: HashSet<String>,
 
-    /// List of available versions for the framework (only for web).
-    pub framework_versions: HashSet<String>,
+    /// Runs with their framework version and configuration (only for web).
+    pub runs: Vec<ExistingRun>,
 }
 
-impl Default for ResultMap {
-    fn default() -> Self {
-        ResultMap {
-            computation: HashMap::new(),
-            web: HashMap::new(),
-        }
+pub struct ExistingRun {
+    pub language_version: String,
+    pub framework_version: String,
+
+    /// Values of the configuration columns, e.g. protocol -> h2c
+    pub configuration: HashMap<String, String>,
+}
+
+impl ExistingResult {
+    /// Returns true if there is a run of the versions with the same configuration.
+    pub fn contains_run(
+        &self,
+        language_version: &str,
+        framework_version: &str,
+        configuration: &[(&str, String)],
+    ) -> bool {
+        self.runs.iter().any(|run| {
+            run.language_version == language_version
+                && run.framework_version == framework_version
+                && configuration
+                    .iter()
+                    .all(|(column, value)| run.configuration.get(*column) == Some(value))
+        })
     }
 }
 
@@ -48,6 +76,7 @@ pub fn read_existing_result_map() -> ResultMap {
             dir: 3,
             language_version: 2,
             framework_version: None,
+            configuration: &[],
         },
         &mut result_map.computation,
     );
@@ -58,6 +87,7 @@ pub fn read_existing_result_map() -> ResultMap {
             dir: 9,
             language_version: 2,
             framework_version: Some(7),
+            configuration: WEB_CONFIGURATION_COLUMNS,
         },
         &mut result_map.web,
     );
@@ -69,6 +99,9 @@ struct CsvStructure {
     dir: usize,
     language_version: usize,
     framework_version: Option<usize>,
+
+    /// Columns looked up by name because older files do not have them.
+    configuration: &'static [&'static str],
 }
 
 fn read_from_csv(
@@ -76,7 +109,7 @@ fn read_from_csv(
     csv_structure: CsvStructure,
     map: &mut HashMap<String, HashMap<String, ExistingResult>>,
 ) {
-    let csv_content: String = fs::read_to_string(csv_path).unwrap_or(String::new());
+    let csv_content: String = fs::read_to_string(csv_path).unwrap_or_default();
     read_from_csv_content(csv_content.as_str(), csv_structure, map);
 }
 
@@ -86,7 +119,16 @@ fn read_from_csv_content(
     map: &mut HashMap<String, HashMap<String, ExistingResult>>,
 ) {
     let mut language_versions: HashMap<(String, String), HashSet<String>> = HashMap::new();
-    let mut framework_versions: HashMap<(String, String), HashSet<String>> = HashMap::new();
+    let mut runs: HashMap<(String, String), Vec<ExistingRun>> = HashMap::new();
+
+    let header: Vec<&str> = csv_content
+        .split("\n")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .split(",")
+        .collect();
+    let configuration_index = |column: &str| header.iter().position(|name| *name == column);
 
     csv_content.split("\n").skip(1).for_each(|line| {
         let line = line.trim();
@@ -116,32 +158,46 @@ fn read_from_csv_content(
             let key = (language.to_string(), variant.to_string());
             language_versions
                 .entry(key.clone())
-                .or_insert_with(HashSet::new)
+                .or_default()
                 .insert(language_version);
         }
 
-        // Framework version
+        // Framework version and configuration
         if let Some(framework_version_index) = csv_structure.framework_version {
-            let framework_version = columns[framework_version_index].to_string();
+            let configuration = csv_structure
+                .configuration
+                .iter()
+                .filter_map(|column| {
+                    let value = match configuration_index(column) {
+                        Some(index) => columns.get(index).copied(),
+                        None => LEGACY_COLUMN_DEFAULTS
+                            .iter()
+                            .find(|(name, _)| name == column)
+                            .map(|(_, value)| *value),
+                    };
+                    value.map(|value| (column.to_string(), value.to_string()))
+                })
+                .collect();
             let key = (language.to_string(), variant.to_string());
-            framework_versions
-                .entry(key)
-                .or_insert_with(HashSet::new)
-                .insert(framework_version);
+            runs.entry(key).or_default().push(ExistingRun {
+                language_version: columns[csv_structure.language_version].to_string(),
+                framework_version: columns[framework_version_index].to_string(),
+                configuration,
+            });
         }
     });
 
     // Final map construction
     for ((language, variant), lang_versions) in language_versions {
-        let variant_map = map.entry(language.clone()).or_insert_with(HashMap::new);
+        let variant_map = map.entry(language.clone()).or_default();
 
         let existing_result = ExistingResult {
             language: language.clone(),
             variant: variant.clone(),
             language_versions: lang_versions.into_iter().collect(),
-            framework_versions: framework_versions
-                .get(&(language.clone(), variant.clone()))
-                .map_or_else(HashSet::new, |set| set.iter().cloned().collect()),
+            runs: runs
+                .remove(&(language.clone(), variant.clone()))
+                .unwrap_or_default(),
         };
 
         variant_map.insert(variant, existing_result);
@@ -151,6 +207,55 @@ fn read_from_csv_content(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::cpu_affinity::CpuSet;
+    use crate::utils::result_writer::write_result_to_file;
+
+    fn take_new_values<'a>(_: &'a [&'a str], new_values: &'a [&'a str]) -> &'a [&'a str] {
+        new_values
+    }
+
+    #[test]
+    fn should_read_written_row_with_multi_range_cpuset() {
+        let path = std::env::temp_dir().join(format!("sharkbench_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let cpuset = "0-1,4".parse::<CpuSet>().unwrap().label();
+        write_result_to_file(
+            path,
+            &vec![
+                ("language", "Rust"),
+                ("version", "1.86"),
+                ("framework_version", "0.7"),
+                ("path", "rust/axum-rust"),
+                ("load_cpuset", cpuset.as_str()),
+                ("container_cpuset", "2-3"),
+            ],
+            &vec![("rps_median", "1000")],
+            &[],
+            take_new_values,
+        )
+        .unwrap();
+        let contents = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[1].split(",").count(), lines[0].split(",").count());
+        assert_eq!(lines[1].split(",").nth(4), Some("0-1+4"));
+
+        let mut map = HashMap::new();
+        read_from_csv_content(
+            &contents,
+            CsvStructure {
+                dir: 3,
+                language_version: 1,
+                framework_version: Some(2),
+                configuration: &[],
+            },
+            &mut map,
+        );
+        let axum = &map["rust"]["axum-rust"];
+        assert!(axum.language_versions.contains("1.86"));
+        assert!(axum.contains_run("1.86", "0.7", &[]));
+    }
 
     #[test]
     fn test_read_from_csv_content() {
@@ -167,6 +272,7 @@ mod tests {
                 dir: 9,
                 language_version: 2,
                 framework_version: Some(6),
+                configuration: &[],
             },
             &mut map,
         );
@@ -186,8 +292,9 @@ mod tests {
         assert_eq!(springboot.language_versions.len(), 2);
         assert!(springboot.language_versions.contains("8"));
         assert!(springboot.language_versions.contains("17"));
-        assert_eq!(springboot.framework_versions.len(), 1);
-        assert!(springboot.framework_versions.contains("3"));
+        assert_eq!(springboot.runs.len(), 2);
+        assert!(springboot.contains_run("8", "3", &[]));
+        assert!(springboot.contains_run("17", "3", &[]));
 
         // Check PHP
         let php_map = map.get("php").expect("PHP should exist");
@@ -201,9 +308,10 @@ mod tests {
         assert_eq!(symfony.language_versions.len(), 2);
         assert!(symfony.language_versions.contains("7"));
         assert!(symfony.language_versions.contains("8"));
-        assert_eq!(symfony.framework_versions.len(), 2);
-        assert!(symfony.framework_versions.contains("4.0"));
-        assert!(symfony.framework_versions.contains("5.0"));
+        assert_eq!(symfony.runs.len(), 2);
+        assert!(symfony.contains_run("7", "4.0", &[]));
+        assert!(symfony.contains_run("8", "5.0", &[]));
+        assert!(!symfony.contains_run("7", "5.0", &[]));
 
         // Check Rust
         let rust_map = map.get("rust").expect("Rust should exist");
@@ -214,7 +322,41 @@ mod tests {
         assert_eq!(actix.variant, "actix-rust");
         assert_eq!(actix.language_versions.len(), 1);
         assert!(actix.language_versions.contains("1.67"));
-        assert_eq!(actix.framework_versions.len(), 1);
-        assert!(actix.framework_versions.contains("4"));
+        assert_eq!(actix.runs.len(), 1);
+        assert!(actix.contains_run("1.67", "4", &[]));
+    }
+
+    #[test]
+    fn should_tell_runs_apart_by_configuration() {
+        let mut map = HashMap::new();
+        read_from_csv_content(
+            r#"language,version,framework_version,path,protocol,engine
+Rust,1.86,0.7,rust/axum-rust,http1,reqwest
+Rust,1.86,0.8,rust/axum-rust,h2c,hyper
+"#,
+            CsvStructure {
+                dir: 3,
+                language_version: 1,
+                framework_version: Some(2),
+                configuration: WEB_CONFIGURATION_COLUMNS,
+            },
+            &mut map,
+        );
+        let axum = &map["rust"]["axum-rust"];
+
+        let configuration = |protocol: &str, engine: &str| {
+            vec![
+                ("protocol", protocol.to_string()),
+                ("engine", engine.to_string()),
+            ]
+        };
+        assert!(axum.contains_run("1.86", "0.7", &configuration("http1", "reqwest")));
+        assert!(!axum.contains_run("1.86", "0.7", &configuration("h2c", "hyper")));
+        assert!(axum.contains_run("1.86", "0.8", &configuration("h2c", "hyper")));
+
+        // Older files do not have the column and only contain runs of the default.
+        let load_mode = |load_mode: &str| vec![("load_mode", load_mode.to_string())];
+        assert!(axum.contains_run("1.86", "0.7", &load_mode("closed")));
+        assert!(!axum.contains_run("1.86", "0.7", &load_mode("open")));
     }
 }
@@ -13,13 +13,18 @@ use std::{fs, io};
 /// `values` contains the keys and values for the columns that contain the benchmark results.
 /// Example: `vec![("time", "1234"), ("memory", "1234")]`.
 ///
+/// `defaults` contains the values of columns that rows written by an older version do not have yet.
+/// Such rows are rearranged to the current header, missing columns without a default stay empty.
+/// Example: `&[("protocol", "http1")]`.
+///
 /// `on_conflict` is a function that is called when a line with the same descriptor values already exists.
 /// It receives the existing values and the new values and should return the values that should be written to the file.
 pub fn write_result_to_file(
     file_path: &str,
     descriptors: &Vec<(&str, &str)>,
     values: &Vec<(&str, &str)>,
-    on_conflict: for<'a> fn(&'a [&'a str], &'a [&'a str]) -> &'a [&'a str],
+    defaults: &[(&str, &str)],
+    on_conflict: impl for<'a> Fn(&'a [&'a str], &'a [&'a str]) -> &'a [&'a str],
 ) -> io::Result<()> {
     println!(" -> Writing result:");
     for (key, value) in descriptors {
@@ -43,7 +48,11 @@ pub fn write_result_to_file(
         format!("{},{}", descriptor_keys, values_keys)
     };
 
-    let old_contents = fs::read_to_string(file_path).unwrap_or(String::new());
+    let old_contents = migrate_contents(
+        &fs::read_to_string(file_path).unwrap_or_default(),
+        &header,
+        defaults,
+    );
 
     let descriptor_values: Vec<&str> = descriptors.iter().map(|(_, v)| *v).collect::<Vec<&str>>();
     let value_values: Vec<&str> = values.iter().map(|(_, v)| *v).collect::<Vec<&str>>();
@@ -70,11 +79,43 @@ pub fn write_result_to_file(
     Ok(())
 }
 
+/// Rearranges the rows of `old_contents` to the columns of `header`.
+/// Columns are matched by name, new columns are filled from `defaults` or left empty.
+fn migrate_contents(old_contents: &str, header: &str, defaults: &[(&str, &str)]) -> String {
+    let Some(old_header) = old_contents.lines().next() else {
+        return String::new();
+    };
+    if old_header == header {
+        return old_contents.to_string();
+    }
+
+    let old_keys: Vec<&str> = old_header.split(",").collect();
+    let mut buffer = format!("{}\n", header);
+    for line in old_contents.lines().skip(1) {
+        let columns: Vec<&str> = line.split(",").collect();
+        let migrated: Vec<&str> = header
+            .split(",")
+            .map(
+                |key| match old_keys.iter().position(|old_key| *old_key == key) {
+                    Some(index) => columns.get(index).copied().unwrap_or_default(),
+                    None => defaults
+                        .iter()
+                        .find(|(default_key, _)| *default_key == key)
+                        .map_or("", |(_, value)| *value),
+                },
+            )
+            .collect();
+        buffer.push_str(&migrated.join(","));
+        buffer.push('\n');
+    }
+    buffer
+}
+
 fn get_updated_contents(
     old_contents: &str,
     descriptor_values: &[&str],
     value_values: &[&str],
-    on_conflict: for<'a> fn(&'a [&'a str], &'a [&'a str]) -> &'a [&'a str],
+    on_conflict: impl for<'a> Fn(&'a [&'a str], &'a [&'a str]) -> &'a [&'a str],
 ) -> Vec<String> {
     let old_lines: Vec<Vec<&str>> = old_contents
         .lines()
@@ -90,14 +131,12 @@ fn get_updated_contents(
             if columns.starts_with(descriptor_values) {
                 found = true;
                 let mut new_line: Vec<&str> = Vec::new();
-                for i in 0..descriptor_values.len() {
-                    new_line.push(descriptor_values[i]);
-                }
+                new_line.extend_from_slice(descriptor_values);
 
                 let old_values: &[&str] = &columns[descriptor_values.len()..columns.len()];
                 let result: &[&str] = on_conflict(old_values, value_values);
                 for value in result {
-                    new_line.push(&value);
+                    new_line.push(value);
                 }
                 temp_lines.push(new_line);
             } else {
@@ -119,7 +158,7 @@ fn get_updated_contents(
     new_lines.iter().map(|line| line.join(",")).collect()
 }
 
-fn write_lines_to_file(file_path: &str, lines: &Vec<String>, header: &String) -> io::Result<()> {
+fn write_lines_to_file(file_path: &str, lines: &Vec<String>, header: &str) -> io::Result<()> {
     let output: String = {
         let mut buffer = String::new();
 
@@ -129,7 +168,7 @@ fn write_lines_to_file(file_path: &str, lines: &Vec<String>, header: &String) ->
 
         // Data
         for line in lines {
-            buffer.push_str(&line);
+            buffer.push_str(line);
             buffer.push('\n');
         }
 
@@ -140,11 +179,11 @@ fn write_lines_to_file(file_path: &str, lines: &Vec<String>, header: &String) ->
     let parent_dir = Path::new(file_path).parent().unwrap();
     if !parent_dir.exists() {
         fs::create_dir_all(parent_dir)
-            .expect(format!("Failed to create directory {}", parent_dir.display()).as_str());
+            .unwrap_or_else(|_| panic!("Failed to create directory {}", parent_dir.display()));
     }
 
     fs::write(file_path, output.as_bytes())
-        .expect(format!("Failed to write {}", file_path).as_str());
+        .unwrap_or_else(|_| panic!("Failed to write {}", file_path));
 
     Ok(())
 }
@@ -152,6 +191,7 @@ fn write_lines_to_file(file_path: &str, lines: &Vec<String>, header: &String) ->
 /// Compares two lines column-wise. Both lines must have the same number of columns.
 /// If a column is detected as a version number, it will be compared as such.
 /// Example: 1.2 < 1.10 (as opposed to 1.2 > 1.10 when comparing as strings)
+#[allow(clippy::ptr_arg)]
 fn compare_lines(a: &Vec<&str>, b: &Vec<&str>) -> Ordering {
     for i in 0..a.len() {
         let a_value = a[i];
@@ -164,7 +204,7 @@ fn compare_lines(a: &Vec<&str>, b: &Vec<&str>) -> Ordering {
                     ordering => return ordering,
                 }
             }
-            _ => match a_value.cmp(&b_value) {
+            _ => match a_value.cmp(b_value) {
                 Ordering::Equal => continue, // check next column
                 ordering => return ordering,
             },
@@ -230,6 +270,31 @@ mod tests {
         }
     }
 
+    mod migrate_contents {
+        use super::*;
+
+        #[test]
+        fn should_keep_contents_with_same_header() {
+            let contents = "a,b,x\n1,2,3\n";
+            assert_eq!(migrate_contents(contents, "a,b,x", &[]), contents);
+        }
+
+        #[test]
+        fn should_fill_new_columns_with_defaults() {
+            let contents = "a,b,x,y\n1,2,3,4\n5,6,7,8\n";
+            assert_eq!(
+                migrate_contents(contents, "a,b,c,x,y,z", &[("c", "default")]),
+                "a,b,c,x,y,z\n1,2,default,3,4,\n5,6,default,7,8,\n"
+            );
+        }
+
+        #[test]
+        fn should_drop_removed_columns() {
+            let contents = "a,b,x\n1,2,3\n";
+            assert_eq!(migrate_contents(contents, "a,x", &[]), "a,x\n1,3\n");
+        }
+    }
+
     mod get_updated_contents {
         use super::*;
 
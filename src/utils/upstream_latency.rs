@@ -0,0 +1,164 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Latency of the web data source, to see how a framework behaves when its I/O actually waits.
+/// The timer of the data source has a resolution of 1 ms, so the delays are whole milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LatencyProfile {
+    /// No added latency, or the latency set by the environment variables of the data source.
+    None,
+
+    /// 1 ms plus up to 1 ms of uniform jitter, like a service in the same network.
+    Lan,
+
+    /// 2 ms plus log-normal jitter, 1% of the responses take 50 ms longer, like a database.
+    Database,
+
+    /// 10 ms plus log-normal jitter, 0.5% of the responses take 250 ms longer, like a managed service in another zone.
+    Cloud,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Jitter {
+    /// Adds a uniformly distributed delay between 0 and `max`.
+    Uniform { max: Duration },
+
+    /// Adds a log-normally distributed delay, which has a long tail.
+    LogNormal { median: Duration, sigma: f64 },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Latency {
+    /// Added to every response.
+    pub delay: Duration,
+    pub jitter: Option<Jitter>,
+
+    /// Fraction of the responses that additionally take `slow_delay`.
+    pub slow_rate: f64,
+    pub slow_delay: Duration,
+}
+
+impl LatencyProfile {
+    pub fn latency(&self) -> Option<Latency> {
+        match self {
+            LatencyProfile::None => None,
+            LatencyProfile::Lan => Some(Latency {
+                delay: Duration::from_millis(1),
+                jitter: Some(Jitter::Uniform {
+                    max: Duration::from_millis(1),
+                }),
+                slow_rate: 0.0,
+                slow_delay: Duration::ZERO,
+            }),
+            LatencyProfile::Database => Some(Latency {
+                delay: Duration::from_millis(2),
+                jitter: Some(Jitter::LogNormal {
+                    median: Duration::from_millis(2),
+                    sigma: 0.5,
+                }),
+                slow_rate: 0.01,
+                slow_delay: Duration::from_millis(50),
+            }),
+            LatencyProfile::Cloud => Some(Latency {
+                delay: Duration::from_millis(10),
+                jitter: Some(Jitter::LogNormal {
+                    median: Duration::from_millis(5),
+                    sigma: 0.8,
+                }),
+                slow_rate: 0.005,
+                slow_delay: Duration::from_millis(250),
+            }),
+        }
+    }
+
+    /// Path and query of the control endpoint of the data source that sets this profile.
+    /// Without a query, the data source falls back to its environment variables.
+    pub fn control_path(&self) -> String {
+        let Some(latency) = self.latency() else {
+            return "/latency".to_string();
+        };
+        let jitter = match latency.jitter {
+            None => "jitter=none".to_string(),
+            Some(Jitter::Uniform { max }) => {
+                format!("jitter=uniform&jitter_us={}", max.as_micros())
+            }
+            Some(Jitter::LogNormal { median, sigma }) => format!(
+                "jitter=lognormal&jitter_us={}&sigma={}",
+                median.as_micros(),
+                sigma
+            ),
+        };
+        format!(
+            "/latency?delay_us={}&{}&slow_rate={}&slow_us={}",
+            latency.delay.as_micros(),
+            jitter,
+            latency.slow_rate,
+            latency.slow_delay.as_micros()
+        )
+    }
+}
+
+impl LatencyProfile {
+    /// Label of the profile for the results, given the latency the data source reports as in effect.
+    /// With the profile `none`, the data source adds the latency of its environment variables,
+    /// which is recorded as `env:<parameters>`.
+    pub fn label(&self, effective: &str) -> String {
+        match (self, effective) {
+            (LatencyProfile::None, "none") => self.to_string(),
+            (LatencyProfile::None, effective) => format!("env:{effective}"),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl Display for LatencyProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LatencyProfile::None => write!(f, "none"),
+            LatencyProfile::Lan => write!(f, "lan"),
+            LatencyProfile::Database => write!(f, "database"),
+            LatencyProfile::Cloud => write!(f, "cloud"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_build_control_path() {
+        assert_eq!(LatencyProfile::None.control_path(), "/latency");
+        assert_eq!(
+            LatencyProfile::Lan.control_path(),
+            "/latency?delay_us=1000&jitter=uniform&jitter_us=1000&slow_rate=0&slow_us=0"
+        );
+        assert_eq!(
+            LatencyProfile::Database.control_path(),
+            "/latency?delay_us=2000&jitter=lognormal&jitter_us=2000&sigma=0.5&slow_rate=0.01&slow_us=50000"
+        );
+    }
+
+    #[test]
+    fn should_label_latency_from_environment() {
+        assert_eq!(LatencyProfile::None.label("none"), "none");
+        assert_eq!(
+            LatencyProfile::None.label("delay_us=2000&jitter=none&slow_rate=0&slow_us=0"),
+            "env:delay_us=2000&jitter=none&slow_rate=0&slow_us=0"
+        );
+        assert_eq!(
+            LatencyProfile::Lan
+                .label("delay_us=1000&jitter=uniform&jitter_us=1000&slow_rate=0&slow_us=0"),
+            "lan"
+        );
+    }
+
+    #[test]
+    fn should_parse_profile_from_yaml() {
+        let profile: LatencyProfile = serde_yaml::from_str("database").unwrap();
+        assert_eq!(profile, LatencyProfile::Database);
+    }
+}
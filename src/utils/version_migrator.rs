@@ -45,7 +45,7 @@ impl VersionMigrator {
             ) {
                 Ok(new_contents) => {
                     std::fs::write(&t.path, new_contents)
-                        .expect(format!("Could not write {}", t.path).as_str());
+                        .unwrap_or_else(|_| panic!("Could not write {}", t.path));
                 }
                 Err(e) => match e {
                     MigrationError::VersionNotFound => {
@@ -70,15 +70,14 @@ impl VersionMigrator {
 
     pub fn restore(&self) {
         for t in &self.transformations {
-            let contents = t.original.as_ref().expect(
-                format!(
+            let contents = t.original.as_ref().unwrap_or_else(|| {
+                panic!(
                     "Could not restore {} (original not found). This should not happen.",
                     t.path
                 )
-                .as_str(),
-            );
+            });
             std::fs::write(&t.path, contents)
-                .expect(format!("Could not write {}", t.path).as_str());
+                .unwrap_or_else(|_| panic!("Could not write {}", t.path));
         }
     }
 
@@ -86,7 +85,7 @@ impl VersionMigrator {
     fn load_original_contents(&mut self) {
         for t in &mut self.transformations {
             let contents = std::fs::read_to_string(&t.path)
-                .expect(format!("Could not read {}", t.path).as_str());
+                .unwrap_or_else(|_| panic!("Could not read {}", t.path));
             t.original = Some(contents);
         }
     }